@@ -1,55 +1,465 @@
-use super::util::take_2;
-use crate::typing::Problem;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::budget::{Budget, StopReason};
+use super::moves::{Move, MoveParams, RangeLimiter, RangeParams};
+use super::observer::{Control, Observer, Step};
+use super::pareto::{ParetoArchive, ParetoParams};
+use crate::analysis::{
+    route, CongestionCost, CongestionParams, PowerCost, PowerParams, RouterParams, RoutingResult,
+    TimingCost, TimingGraph, TimingParams,
+};
+use crate::typing::{Coor, Placement, Problem};
+
+pub enum Schedule {
+    // multiply t by a constant factor from `t_init` until below `t_terminate`
+    Fixed {
+        t_init: f32,
+        t_decrease_factor: f32,
+        t_terminate: f32,
+    },
+    // VPR's: t starts at 20 times the standard deviation of the cost over
+    // n_pin random moves, cools faster while nearly every move or nearly
+    // none is accepted, and stops once below 0.005 * cost / n_nets
+    Adaptive,
+}
+
+impl Schedule {
+    // the factor the temperature is multiplied by after a temperature at
+    // which `acceptance` of the moves were accepted
+    fn alpha(&self, acceptance: f64, window_open: bool) -> f32 {
+        match self {
+            Schedule::Fixed {
+                t_decrease_factor, ..
+            } => *t_decrease_factor,
+            Schedule::Adaptive if acceptance > 0.96 => 0.5,
+            Schedule::Adaptive if acceptance > 0.8 => 0.9,
+            // a window that can still shrink keeps the acceptance up by itself
+            Schedule::Adaptive if acceptance > 0.15 || window_open => 0.95,
+            Schedule::Adaptive => 0.8,
+        }
+    }
+}
 
 pub struct Params {
-    pub t_init: f32,
-    pub t_decrease_factor: f32,
-    pub t_terminate: f32,
+    pub schedule: Schedule,
+    // moves per temperature: inner_num * n_pin^(4/3)
+    pub inner_num: f32,
+    // compare the cached cost against a full recompute every n moves
+    pub check_every: Option<usize>,
+    // the mix of moves to draw from
+    pub moves: MoveParams,
+    // draw moves within a window resized every temperature to keep the
+    // acceptance rate near its target
+    pub range_limit: Option<RangeParams>,
+    // add a weighted RUDY overflow term to the wirelength cost
+    pub congestion: Option<CongestionParams>,
+    // optimize lambda * timing + (1 - lambda) * wirelength
+    pub timing: Option<TimingParams>,
+    // add the activity-weighted wirelength to keep high-toggle nets short
+    pub power: Option<PowerParams>,
+    // keep the non-dominated placements seen at the end of temperatures
+    pub pareto: Option<ParetoParams>,
+    // globally route the final placement
+    pub route: Option<RouterParams>,
+    // stop early after a time or a number of moves, or when cancelled
+    pub budget: Budget,
 }
 
-pub fn annealing_placement(problem: &Problem, params: &Params) {
-    let mut t = params.t_init;
+#[derive(Clone)]
+pub struct TemperatureStats {
+    pub t: f32,
+    pub acceptance: f64,
+    // the cost at the end of the temperature
+    pub cost: f64,
+    // of the cost over the moves of the temperature
+    pub std_dev: f64,
+}
+
+pub struct AnnealingResult {
+    // the placement with the lowest cost at the end of any temperature; a
+    // better one passed through in the middle of a temperature is not kept
+    pub best: Placement,
+    // the costs of `best` and of the last placement, with every term; the
+    // timing term is scaled as in the temperature it ended
+    pub cost: f64,
+    pub final_cost: f64,
+    // of `best`: the cost of the cost model alone and the critical delay
+    pub wirelength: f64,
+    pub critical_delay: f64,
+    pub elapsed: Duration,
+    pub n_moves: usize,
+    pub n_accepted: usize,
+    pub history: Vec<TemperatureStats>,
+    pub stop_reason: StopReason,
+    // with `params.pareto`
+    pub pareto: Option<ParetoArchive>,
+    // of `best`, with `params.route`
+    pub routing: Option<RoutingResult>,
+}
+
+impl AnnealingResult {
+    // see `ConsoleObserver` for the temperatures as they pass
+    pub fn print(&self) {
+        println!(
+            "best={:.0}   final={:.0}   wirelength={:.0}   critical_delay={:.2}   temperatures={}   moves={}   accepted={}   time={:.2}s   stop={:?}",
+            self.cost,
+            self.final_cost,
+            self.wirelength,
+            self.critical_delay,
+            self.history.len(),
+            self.n_moves,
+            self.n_accepted,
+            self.elapsed.as_secs_f64(),
+            self.stop_reason
+        );
+    }
+}
+
+pub fn annealing_placement(problem: &Arc<Problem>, params: &Params) -> AnnealingResult {
+    annealing_placement_with(problem, params, &mut |_: usize, _: &Step, _: &Placement| {
+        Control::Continue
+    })
+}
+
+// as `annealing_placement`, reporting every temperature to `observer`.
+// the best placement is sampled when a temperature ends
+pub fn annealing_placement_with(
+    problem: &Arc<Problem>,
+    params: &Params,
+    observer: &mut dyn Observer,
+) -> AnnealingResult {
+    let start = Instant::now();
+    // the adaptive schedule first accepts everything, to measure how much
+    // the cost varies
+    let mut t = match params.schedule {
+        Schedule::Fixed { t_init, .. } => t_init,
+        Schedule::Adaptive => f32::INFINITY,
+    };
     let mut i_iter = 0;
-    let n_batch = (100_f32 * (problem.n_pin as f32).powf(4. / 3.)) as usize;
+    let mut i_move: usize = 0;
+    let mut best: Option<(f64, Placement)> = None;
+    let mut stop_reason = None;
+    let n_batch = (params.inner_num * (problem.n_pin as f32).powf(4. / 3.)) as usize;
+    let n_nets = problem.nets.iter().filter(|net| !net.is_global).count();
 
     let mut sol = problem.make_placement();
+    sol.cost_force();
+    let mut n_accepted_total = 0;
+    let mut history = Vec::new();
+    let mut congestion = params
+        .congestion
+        .as_ref()
+        .map(|c| CongestionCost::new(&sol, c));
+    let mut timing = params.timing.as_ref().map(|tp| TimingCost::new(&sol, tp));
+    let mut power = params.power.as_ref().map(|p| PowerCost::new(&sol, p));
+    let mut archive = params
+        .pareto
+        .as_ref()
+        .map(|p| ParetoArchive::new(problem, p));
+    let mut limiter = params
+        .range_limit
+        .as_ref()
+        .map(|r| RangeLimiter::new(problem, r));
 
     use rand::Rng;
     let mut rng = rand::thread_rng();
+    let mut m = Move::new();
     loop {
-        let mut acc_delta: i128 = 0;
-        for _ in 0..n_batch {
-            // randomly select two pins
-            let (ca, cb) = take_2(&problem.coors);
-            // calculate previous cost
-            let cost_prev = sol.cell_cost(ca) + sol.cell_cost(cb);
-            // swap pin position
-            sol.swap(ca, cb);
-            // calculate current cost
-            let cost_curr = sol.cell_cost(ca) + sol.cell_cost(cb);
-            // calculate delta cost
-            let delta_cost: f32 = cost_curr as f32 - cost_prev as f32;
-
-            let r: f32 = rng.gen();
-            if r < f32::exp(-(delta_cost as f32) / t) {
-                acc_delta += delta_cost as i128; // confirm swap
+        // the timing delta is scaled into wirelength units by the ratio of
+        // the two costs at the start of the temperature, so that the
+        // temperature keeps its meaning whatever lambda is
+        let (lambda, timing_scale) = match (params.timing.as_ref(), timing.as_ref()) {
+            (Some(tp), Some(tc)) if tc.cost() > 0. => (tp.lambda, sol.cost_mut() / tc.cost()),
+            _ => (0., 0.),
+        };
+        let mut acc_delta = 0.;
+        let mut n_accepted = 0;
+        // the change in cost since the start of the temperature, summed and
+        // squared over the moves
+        let (mut sum, mut sum_sq) = (0., 0.);
+        let window = limiter.as_ref().map(|l| l.window());
+        let n_moves = if t.is_infinite() {
+            problem.n_pin
+        } else {
+            n_batch
+        };
+        let mut n_done = 0;
+        for _ in 0..params.budget.steps_left(i_move, n_moves) {
+            // a clock read costs about as much as a move
+            if n_done % 64 == 0 {
+                stop_reason = params.budget.check(start, i_move);
+                if stop_reason.is_some() {
+                    break;
+                }
+            }
+            n_done += 1;
+            m.redraw(&sol, &params.moves, window, &mut rng);
+            sol.begin_move();
+            if let Some(c) = congestion.as_mut() {
+                c.begin_move();
+            }
+            if let Some(tc) = timing.as_mut() {
+                tc.begin_move();
+            }
+            if let Some(p) = power.as_mut() {
+                p.begin_move();
+            }
+            // swap pin positions and get the delta cost, keeping the other
+            // cost terms up to date
+            let step = |sol: &mut Placement, ca: Coor, cb: Coor| {
+                if let Some(c) = congestion.as_mut() {
+                    c.before_swap(sol, ca, cb);
+                }
+                let mut delta_cost = sol.apply(ca, cb);
+                if let Some(tc) = timing.as_mut() {
+                    let delta_timing = tc.after_swap(sol, ca, cb);
+                    delta_cost = (1. - lambda) * delta_cost + lambda * timing_scale * delta_timing;
+                }
+                if let Some(c) = congestion.as_mut() {
+                    delta_cost += c.after_swap(sol);
+                }
+                if let Some(p) = power.as_mut() {
+                    delta_cost += p.after_swap(sol, ca, cb);
+                }
+                delta_cost
+            };
+            let delta_cost = m.apply_with(&mut sol, step);
+
+            let r: f64 = rng.gen();
+            if r < f64::exp(-delta_cost / t as f64) {
+                acc_delta += delta_cost; // confirm move
+                n_accepted += 1;
+                sum += acc_delta;
+                sum_sq += acc_delta * acc_delta;
+                sol.commit();
+                if let Some(c) = congestion.as_mut() {
+                    c.commit();
+                }
+                if let Some(tc) = timing.as_mut() {
+                    tc.commit();
+                }
+                if let Some(p) = power.as_mut() {
+                    p.commit();
+                }
             } else {
-                sol.swap(ca, cb); // restore swap
+                // restore move from the journals, re-evaluating nothing
+                sol.rollback();
+                if let Some(c) = congestion.as_mut() {
+                    c.rollback();
+                }
+                if let Some(tc) = timing.as_mut() {
+                    tc.rollback();
+                }
+                if let Some(p) = power.as_mut() {
+                    p.rollback();
+                }
+                sum += acc_delta;
+                sum_sq += acc_delta * acc_delta;
+            }
+
+            i_move += 1;
+            if let Some(n) = params.check_every {
+                if i_move.is_multiple_of(n) {
+                    check_cost(&sol);
+                }
             }
         }
-        println!(
-            "i={:3}   t={:.2}   d_cost={:7}   cost={:5}",
-            i_iter,
+        stop_reason = stop_reason.or_else(|| params.budget.check(start, i_move));
+        // the objective the moves were accepted on
+        let cost = (1. - lambda) * sol.cost_mut()
+            + timing
+                .as_ref()
+                .map_or(0., |tc| lambda * timing_scale * tc.cost())
+            + congestion.as_ref().map_or(0., |c| c.cost())
+            + power.as_ref().map_or(0., |p| p.cost());
+        if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+            best = Some((cost, sol.clone()));
+        }
+        let acceptance = n_accepted as f64 / n_done.max(1) as f64;
+        let n = n_done.max(1) as f64;
+        let std_dev = (sum_sq / n - (sum / n).powi(2)).max(0.).sqrt();
+        n_accepted_total += n_accepted;
+        history.push(TemperatureStats {
             t,
-            acc_delta,
-            sol.cost_force()
+            acceptance,
+            cost,
+            std_dev,
+        });
+        let control = observer.observe(
+            i_iter,
+            &Step::Temperature(history.last().unwrap()),
+            &best.as_ref().unwrap().1,
         );
 
+        let window_open = limiter.as_ref().is_some_and(|l| l.window() > 1);
+        if let Some(l) = limiter.as_mut() {
+            l.update(acceptance);
+        }
+        if let (Some(p), Some(a)) = (params.pareto.as_ref(), archive.as_mut()) {
+            if i_iter % p.every == 0 {
+                a.offer(&sol);
+            }
+        }
+        if let (Some(tp), Some(tc)) = (params.timing.as_ref(), timing.as_mut()) {
+            if (i_iter + 1) % tp.refresh_every == 0 {
+                tc.refresh(&sol);
+            }
+        }
+
         // decrease t
-        t *= params.t_decrease_factor;
-        if t < params.t_terminate {
+        let done = match params.schedule {
+            Schedule::Fixed { t_terminate, .. } => {
+                t *= params.schedule.alpha(acceptance, window_open);
+                t < t_terminate
+            }
+            Schedule::Adaptive => {
+                if t.is_infinite() {
+                    t = 20. * std_dev as f32;
+                } else {
+                    t *= params.schedule.alpha(acceptance, window_open);
+                }
+                t as f64 <= 0.005 * cost / n_nets.max(1) as f64
+            }
+        };
+        if control == Control::Stop {
+            stop_reason = stop_reason.or(Some(StopReason::Observer));
+        }
+        if done {
+            stop_reason = stop_reason.or(Some(StopReason::Completed));
+        }
+        if stop_reason.is_some() {
             break;
         }
         i_iter += 1;
     }
+    let final_cost = history.last().map_or(0., |s| s.cost);
+    let (cost, best) = best.unwrap();
+    let wirelength = best.cost_panic();
+    let critical_delay = TimingGraph::new(problem)
+        .analyze(&best, problem.delay_model.as_ref())
+        .critical_delay;
+    observer.finish(i_iter, &Step::Temperature(history.last().unwrap()), &best);
+    if let Some(a) = archive.as_mut() {
+        a.offer(&best);
+    }
+    let stop_reason = stop_reason.unwrap();
+    // a cancelled run returns at once, without the slow routing
+    let routing = params
+        .route
+        .as_ref()
+        .filter(|_| stop_reason != StopReason::Cancelled)
+        .map(|rp| route(&best, rp));
+    AnnealingResult {
+        best,
+        cost,
+        final_cost,
+        wirelength,
+        critical_delay,
+        elapsed: start.elapsed(),
+        n_moves: i_move,
+        n_accepted: n_accepted_total,
+        history,
+        stop_reason,
+        pareto: archive,
+        routing,
+    }
+}
+
+fn check_cost(sol: &Placement) {
+    let cached = sol.cost_panic();
+    let recomputed = sol.cost_recompute();
+    if (cached - recomputed).abs() > 1e-6 * recomputed.max(1.) {
+        panic!(
+            "incremental cost drifted (cached={}, recomputed={})",
+            cached, recomputed
+        );
+    }
+}
+
+#[test]
+fn adaptive_schedule_should_cool_by_acceptance() {
+    let s = Schedule::Adaptive;
+    assert_eq!(s.alpha(0.99, false), 0.5);
+    assert_eq!(s.alpha(0.9, false), 0.9);
+    assert_eq!(s.alpha(0.44, false), 0.95);
+    assert_eq!(s.alpha(0.1, true), 0.95);
+    assert_eq!(s.alpha(0.1, false), 0.8);
+}
+
+#[test]
+fn adaptive_schedule_should_stop_by_itself() {
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    #[rustfmt::skip]
+    let params = Params { schedule: Schedule::Adaptive, inner_num: 1., check_every: Some(10_000), moves: MoveParams::default(), range_limit: Some(RangeParams::default()), congestion: None, timing: None, power: None, pareto: None, route: None, budget: Budget::default() };
+    let result = annealing_placement(&problem, &params);
+    assert!(result.best.validate().is_empty());
+    assert!(result.cost < problem.make_placement().cost_recompute());
+    assert_eq!(result.cost, result.best.cost_panic());
+    assert!(result.history.iter().all(|s| result.cost <= s.cost));
+    assert!(result.history[0].t.is_infinite() && result.history[0].acceptance == 1.);
+    assert!(result.n_accepted <= result.n_moves);
+}
+
+#[test]
+fn observer_should_see_every_temperature_and_stop_the_run() {
+    use super::observer::MemoryObserver;
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    #[rustfmt::skip]
+    let params = Params { schedule: Schedule::Fixed { t_init: 5., t_decrease_factor: 0.5, t_terminate: 0.1 }, inner_num: 1., check_every: None, moves: MoveParams::default(), range_limit: None, congestion: None, timing: None, power: None, pareto: None, route: None, budget: Budget::default() };
+    let mut memory = MemoryObserver::default();
+    let result = annealing_placement_with(&problem, &params, &mut memory);
+    assert_eq!(memory.temperatures.len(), result.history.len());
+    assert_eq!(*memory.best_costs.last().unwrap(), result.cost);
+
+    let mut stop_at_two = |i: usize, _: &Step, _: &Placement| {
+        if i == 2 {
+            Control::Stop
+        } else {
+            Control::Continue
+        }
+    };
+    let result = annealing_placement_with(&problem, &params, &mut stop_at_two);
+    assert_eq!(result.history.len(), 3);
+}
+
+#[test]
+fn budget_should_stop_the_run_with_a_consistent_best() {
+    use super::budget::CancelToken;
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    #[rustfmt::skip]
+    let mut params = Params { schedule: Schedule::Adaptive, inner_num: 1., check_every: None, moves: MoveParams::default(), range_limit: None, congestion: None, timing: None, power: None, pareto: None, route: None, budget: Budget::default() };
+    params.budget.max_steps = Some(10_000);
+    let result = annealing_placement(&problem, &params);
+    assert_eq!(result.stop_reason, StopReason::StepBudget);
+    assert_eq!(result.n_moves, 10_000);
+    assert!(result.best.validate().is_empty());
+    assert!((result.cost - result.best.cost_recompute()).abs() < 1e-6);
+
+    let token = CancelToken::new();
+    token.cancel();
+    params.budget = Budget {
+        cancel: Some(token),
+        ..Budget::default()
+    };
+    let result = annealing_placement(&problem, &params);
+    assert_eq!(result.stop_reason, StopReason::Cancelled);
+    assert_eq!(result.n_moves, 0);
+    assert!(result.best.validate().is_empty());
+}
+
+#[test]
+fn timing_driven_run_should_keep_the_best_of_the_whole_objective() {
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    #[rustfmt::skip]
+    let params = Params { schedule: Schedule::Fixed { t_init: 5., t_decrease_factor: 0.5, t_terminate: 0.1 }, inner_num: 1., check_every: None, moves: MoveParams::default(), range_limit: None, congestion: None, timing: Some(TimingParams::new(0.9, 1., 1)), power: None, pareto: None, route: None, budget: Budget::default() };
+    let result = annealing_placement(&problem, &params);
+    assert!(result.history.iter().all(|s| result.cost <= s.cost));
+    assert_ne!(result.cost, result.wirelength);
+    assert_eq!(result.wirelength, result.best.cost_panic());
+    assert!(result.critical_delay > 0.);
 }
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// asks a running placer to stop, from another thread or a signal handler
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// when a placer gives up early. it then returns the best placement found so
+// far, as if its run had ended there
+#[derive(Clone, Default)]
+pub struct Budget {
+    pub time: Option<Duration>,
+    // moves for the annealer, generations for the genetic placers
+    pub max_steps: Option<usize>,
+    pub cancel: Option<CancelToken>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    // the schedule or the generations ran out
+    Completed,
+    TimeBudget,
+    StepBudget,
+    Cancelled,
+    // an observer asked to stop
+    Observer,
+}
+
+impl Budget {
+    // why a run started at `start` that took `n_steps` steps has to stop
+    pub fn check(&self, start: Instant, n_steps: usize) -> Option<StopReason> {
+        if self.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            Some(StopReason::Cancelled)
+        } else if self.max_steps.is_some_and(|max| n_steps >= max) {
+            Some(StopReason::StepBudget)
+        } else if self.time.is_some_and(|time| start.elapsed() >= time) {
+            Some(StopReason::TimeBudget)
+        } else {
+            None
+        }
+    }
+
+    // how many of `n` more steps fit into the step budget
+    pub fn steps_left(&self, n_steps: usize, n: usize) -> usize {
+        self.max_steps
+            .map_or(n, |max| n.min(max.saturating_sub(n_steps)))
+    }
+}
+
+#[test]
+fn budget_should_report_what_ran_out() {
+    let start = Instant::now();
+    let mut budget = Budget::default();
+    assert_eq!(budget.check(start, 1_000_000), None);
+    assert_eq!(budget.steps_left(10, 100), 100);
+
+    budget.max_steps = Some(50);
+    assert_eq!(budget.check(start, 49), None);
+    assert_eq!(budget.check(start, 50), Some(StopReason::StepBudget));
+    assert_eq!(budget.steps_left(10, 100), 40);
+    assert_eq!(budget.steps_left(60, 100), 0);
+
+    budget.time = Some(Duration::ZERO);
+    assert_eq!(budget.check(start, 0), Some(StopReason::TimeBudget));
+
+    let token = CancelToken::new();
+    budget.cancel = Some(token.clone());
+    token.cancel();
+    assert_eq!(budget.check(start, 0), Some(StopReason::Cancelled));
+}
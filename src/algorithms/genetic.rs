@@ -1,6 +1,13 @@
 use rand::seq::SliceRandom;
 use rand::Rng;
-
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::budget::{Budget, StopReason};
+use super::moves::{Move, MoveParams, RangeLimiter, RangeParams};
+use super::observer::{Control, Observer, Step};
+use super::pareto::{ParetoArchive, ParetoParams};
+use crate::analysis::{route, RouterParams, RoutingResult};
 use crate::typing::Placement;
 use crate::typing::Problem;
 use crate::typing::{Coor, PinID};
@@ -12,6 +19,17 @@ pub struct Params {
     pub n_select: usize,
     pub n_crossover: usize,
     pub p_mutation: f32,
+    // the mix of moves a mutation draws from
+    pub moves: MoveParams,
+    // keep mutations local, resizing their window every generation from the
+    // fraction of mutations that do not increase the cost
+    pub range_limit: Option<RangeParams>,
+    // keep the non-dominated placements of the population
+    pub pareto: Option<ParetoParams>,
+    // globally route the best placement
+    pub route: Option<RouterParams>,
+    // stop early after a time or a number of generations, or when cancelled
+    pub budget: Budget,
 }
 
 fn selection(
@@ -20,8 +38,8 @@ fn selection(
     padding_cost: bool,
 ) -> Vec<Placement> {
     let cost_max = selection_base.last_mut().unwrap().cost_mut();
-    let mut fitnesses: Vec<(usize, usize)> = if padding_cost {
-        let padding = (cost_max as f32 * 0.01) as usize;
+    let mut fitnesses: Vec<(usize, f64)> = if padding_cost {
+        let padding = cost_max * 0.01;
         selection_base
             .iter_mut()
             .map(|i| cost_max - i.cost_mut() + padding)
@@ -37,13 +55,13 @@ fn selection(
 
     let rng = &mut rand::thread_rng();
     fitnesses.shuffle(rng);
-    let fitness_sum = fitnesses.iter().fold(0, |acc, (_, fit)| acc + fit);
-    let arc_len = fitness_sum / n_survive;
-    let random_offset = (rng.gen::<f32>() * arc_len as f32) as usize;
+    let fitness_sum = fitnesses.iter().fold(0., |acc, (_, fit)| acc + fit);
+    let arc_len = fitness_sum / n_survive as f64;
+    let random_offset = rng.gen::<f64>() * arc_len;
 
     let mut is_selected = vec![false; selection_base.len()];
     let mut pos = random_offset;
-    let mut acc = 0;
+    let mut acc = 0.;
     for (i, fitness) in fitnesses {
         acc += fitness;
         if acc > pos {
@@ -60,13 +78,8 @@ fn selection(
         .collect::<Vec<Placement>>()
 }
 
-#[allow(dead_code)]
-fn make_fixture() -> Problem {
-    let filename = "benchmarks/alu2.blif";
-    use crate::typing::BLIFInfo;
-    let info = BLIFInfo::from_file(filename);
-    Problem::new(&info, 50, 40)
-}
+#[cfg(test)]
+use crate::typing::fixture::make_fixture;
 
 #[test]
 fn test_selection() {
@@ -75,13 +88,13 @@ fn test_selection() {
     population.iter_mut().for_each(|p| {
         p.cost_mut();
     });
-    population.sort_by_cached_key(|i| i.cost_panic());
+    sort_by_cost(&mut population);
     println!(
         "{:?}",
         population
             .iter_mut()
             .map(|i| i.cost_mut())
-            .collect::<Vec<usize>>()
+            .collect::<Vec<f64>>()
     );
     let mut selected = selection(population, 6, true);
     println!(
@@ -89,29 +102,30 @@ fn test_selection() {
         selected
             .iter_mut()
             .map(|i| i.cost_mut())
-            .collect::<Vec<usize>>()
+            .collect::<Vec<f64>>()
     );
 }
 
-fn mutate(placement: &mut Placement) {
-    let (ca, cb) = super::util::take_2(&placement.problem.coors);
-    placement.swap(ca, cb);
+fn sort_by_cost(population: &mut [Placement]) {
+    population.sort_by(|a, b| a.cost_panic().partial_cmp(&b.cost_panic()).unwrap());
+}
+
+// returns the change in cost, which is 0 while the cost is not cached
+pub fn mutate(placement: &mut Placement, moves: &MoveParams, window: Option<usize>) -> f64 {
+    Move::random(placement, moves, window, &mut rand::thread_rng()).apply(placement)
 }
 
 fn improve(mut placement: Placement) -> Option<Placement> {
     let (ca, cb) = super::util::take_2(&placement.problem.coors);
-    let prev = placement.cell_cost(ca) + placement.cell_cost(cb);
-    placement.swap(ca, cb);
-    let curr = placement.cell_cost(ca) + placement.cell_cost(cb);
-    if prev < curr {
+    if placement.swap(ca, cb) > 0. {
         Some(placement)
     } else {
         None
     }
 }
 
-fn crossover<'a>(a: &Placement, b: &Placement, c: &'a mut Placement, d: &'a mut Placement) {
-    let problem = a.problem;
+pub fn crossover<'a>(a: &Placement, b: &Placement, c: &'a mut Placement, d: &'a mut Placement) {
+    let problem = &a.problem;
     let i_divide = (problem.nx as f32 * rand::thread_rng().gen::<f32>()) as usize;
 
     crossover_half(a, b, d, i_divide, &mut rand::thread_rng());
@@ -126,14 +140,16 @@ fn crossover_half<'a, R: Rng>(
     rng: &mut R,
 ) {
     // TODO: vertical division
-    let problem = a.problem;
+    let problem = &a.problem;
 
     // copy b.right to out.right
     let mut out_p2c: Vec<Option<Coor>> = vec![None; problem.n_pin];
+    let mut taken = vec![false; problem.nx * problem.ny];
     for x in i_divide..problem.nx {
         for y in 0..problem.ny {
-            if let Some(pin) = b.coor2pin[x][y] {
-                out_p2c[pin] = Some((x, y));
+            if let Some(pin) = b.coor2pin((x as u16, y as u16)) {
+                out_p2c[pin] = Some((x as u16, y as u16));
+                taken[x * problem.ny + y] = true;
             }
         }
     }
@@ -141,47 +157,34 @@ fn crossover_half<'a, R: Rng>(
     // copy a.left to out.left if not duplicated
     for x in 0..i_divide {
         for y in 0..problem.ny {
-            if let Some(pin) = a.coor2pin[x][y] {
+            if let Some(pin) = a.coor2pin((x as u16, y as u16)) {
                 // ^ if coor (x, y) contains a pin
-                if let Some(_) = out_p2c[pin] {
-                    // ^ if duplicates: pin[a.left] already in c.right
-                    // println!("duplicate :pin={}, coor={:?}", pin, dup_coor);
-                    // duplicated_pins
-                } else {
-                    // ^ no duplication
-                    out.coor2pin[x][y] = a.coor2pin[x][y];
-                    out_p2c[pin] = Some((x, y));
+                if out_p2c[pin].is_none() {
+                    // ^ no duplication: pin[a.left] is not in out.right
+                    out_p2c[pin] = Some((x as u16, y as u16));
+                    taken[x * problem.ny + y] = true;
                 }
-            } else {
-                out.coor2pin[x][y] = None;
             }
         }
     }
 
-    for (pin_id, _) in out_p2c.iter().enumerate().filter(|(_, p)| p.is_none()) {
-        // println!("{}", pin_id);
-        loop {
-            // FIXME it can be highly inefficient when empty cell is limited
-            let (x, y) = *problem.coors.choose(rng).unwrap();
-            if out.coor2pin[x][y].is_none() {
-                out.coor2pin[x][y] = Some(pin_id);
-                break;
-            }
-        }
-    }
-
-    let mut d_new_pin2coor: Vec<Option<Coor>> = vec![None; problem.n_pin];
-    for x in 0..problem.nx {
-        for y in 0..problem.ny {
-            if let Some(pin) = out.coor2pin[x][y] {
-                d_new_pin2coor[pin] = Some((x, y));
-            }
-        }
-    }
-
-    for i_pin in 0..problem.n_pin {
-        out.pin2coor[i_pin] = d_new_pin2coor[i_pin].unwrap();
-    }
+    let pin2coor: Vec<Coor> = out_p2c
+        .iter()
+        .map(|coor| match coor {
+            Some(coor) => *coor,
+            None => loop {
+                // FIXME it can be highly inefficient when empty cell is limited
+                let (x, y) = *problem.coors.choose(rng).unwrap();
+                let i = x as usize * problem.ny + y as usize;
+                if !taken[i] {
+                    taken[i] = true;
+                    break (x, y);
+                }
+            },
+        })
+        .collect();
+    out.set_coors(&pin2coor);
+    debug_assert!(out.validate().is_empty(), "{:?}", out.validate());
 }
 
 #[test]
@@ -193,36 +196,11 @@ fn should_crossover_without_problem() {
     crossover_half(&a, &b, &mut out, problem.nx / 2, &mut rand::thread_rng());
 }
 
-#[allow(dead_code)]
-fn derive_pin2coor(
-    coor2pin: &Vec<Vec<Option<PinID>>>,
-    nx: usize,
-    ny: usize,
-    n_pin: usize,
-) -> Vec<Coor> {
-    let mut d_new_pin2coor: Vec<Option<Coor>> = vec![None; n_pin];
-    for x in 0..nx {
-        for y in 0..ny {
-            if let Some(pin) = coor2pin[x][y] {
-                d_new_pin2coor[pin] = Some((x, y));
-            }
-        }
-    }
-    let mut pin2coor = Vec::new();
-    for i_pin in 0..n_pin {
-        pin2coor.push(d_new_pin2coor[i_pin].unwrap());
-    }
-    pin2coor
-}
-
 #[test]
 fn should_crossover_if_no_overlap() {
     let (nx, ny) = (4, 3);
     let n_pin = 3;
-
-    use crate::typing::make_coors;
-    #[rustfmt::skip]
-    let problem = Problem { nx, ny, nets: vec![], n_pin, pins: vec![], coors: make_coors(nx, ny) };
+    let problem = Arc::new(Problem::from_nets(nx, ny, n_pin, vec![]));
 
     #[rustfmt::skip]
     let coor2pin = vec![
@@ -232,9 +210,7 @@ fn should_crossover_if_no_overlap() {
         vec![Some(1), None  ,  None ], // x=2
         vec![None,    Some(2), None], // x=3 
     ];
-    let pin2coor = derive_pin2coor(&coor2pin, nx, ny, n_pin);
-    #[rustfmt::skip]
-    let a = Placement { problem: &problem, coor2pin, pin2coor, _cost:None };
+    let a = Placement::from_grid(&problem, coor2pin);
 
     #[rustfmt::skip]
     let coor2pin = vec![
@@ -244,16 +220,14 @@ fn should_crossover_if_no_overlap() {
         vec![None,    Some(1), None   ], // x=2
         vec![None,    None,    Some(2)], // x=3 
     ];
-    let pin2coor = derive_pin2coor(&coor2pin, nx, ny, n_pin);
-    #[rustfmt::skip]
-    let b = Placement { problem: &problem, coor2pin, pin2coor, _cost:None };
+    let b = Placement::from_grid(&problem, coor2pin);
 
     let mut out = b.clone();
     crossover_half(&a, &b, &mut out, 2, &mut rand::thread_rng());
 
-    print_coor2pin(&problem, &out.coor2pin);
+    print_coor2pin(&problem, &out.to_grid());
     #[rustfmt::skip]
-    assert_eq!(&out.coor2pin, &vec![
+    assert_eq!(&out.to_grid(), &vec![
         //   y=0      y=1      y=2
         vec![None,    Some(0), None   ], // x=0
         vec![None,    None,    None   ], // x=1
@@ -266,10 +240,7 @@ fn should_crossover_if_no_overlap() {
 fn should_crossover_if_a_left_b_right_do_not_cover_all_pins() {
     let (nx, ny) = (4, 3);
     let n_pin = 3;
-
-    use crate::typing::make_coors;
-    #[rustfmt::skip]
-    let problem = Problem { nx, ny, nets: vec![], n_pin, pins: vec![], coors: make_coors(nx, ny) };
+    let problem = Arc::new(Problem::from_nets(nx, ny, n_pin, vec![]));
 
     #[rustfmt::skip]
     let coor2pin = vec![
@@ -279,9 +250,7 @@ fn should_crossover_if_a_left_b_right_do_not_cover_all_pins() {
         vec![Some(1), None  ,  None   ], // x=2
         vec![None,    Some(2), None   ], // x=3 
     ];
-    let pin2coor = derive_pin2coor(&coor2pin, nx, ny, n_pin);
-    #[rustfmt::skip]
-    let a = Placement { problem: &problem, coor2pin, pin2coor, _cost:None };
+    let a = Placement::from_grid(&problem, coor2pin);
 
     #[rustfmt::skip]
     let coor2pin = vec![
@@ -291,26 +260,21 @@ fn should_crossover_if_a_left_b_right_do_not_cover_all_pins() {
         vec![None,    None,    None   ], // x=2
         vec![None,    None,    Some(2)], // x=3 
     ];
-    let pin2coor = derive_pin2coor(&coor2pin, nx, ny, n_pin);
-    #[rustfmt::skip]
-    let b = Placement { problem: &problem, coor2pin, pin2coor, _cost:None };
+    let b = Placement::from_grid(&problem, coor2pin);
 
     let mut out = b.clone();
     crossover_half(&a, &b, &mut out, 2, &mut rand::thread_rng());
 
-    print_coor2pin(&problem, &out.coor2pin);
-    assert_eq!(&out.coor2pin[3][2], &Some(2));
-    assert_eq!(&out.coor2pin[0][1], &Some(0));
+    print_coor2pin(&problem, &out.to_grid());
+    assert_eq!(out.coor2pin((3, 2)), Some(2));
+    assert_eq!(out.coor2pin((0, 1)), Some(0));
 }
 
 #[test]
 fn should_crossover_if_a_left_b_right_have_overlapped_pins() {
     let (nx, ny) = (4, 3);
     let n_pin = 3;
-
-    use crate::typing::make_coors;
-    #[rustfmt::skip]
-    let problem = Problem { nx, ny, nets: vec![], n_pin, pins: vec![], coors: make_coors(nx, ny) };
+    let problem = Arc::new(Problem::from_nets(nx, ny, n_pin, vec![]));
 
     #[rustfmt::skip]
     let coor2pin = vec![
@@ -320,9 +284,7 @@ fn should_crossover_if_a_left_b_right_have_overlapped_pins() {
         vec![None,    None  ,  None   ], // x=2
         vec![None,    Some(2), None   ], // x=3 
     ];
-    let pin2coor = derive_pin2coor(&coor2pin, nx, ny, n_pin);
-    #[rustfmt::skip]
-    let a = Placement { problem: &problem, coor2pin, pin2coor, _cost:None };
+    let a = Placement::from_grid(&problem, coor2pin);
 
     #[rustfmt::skip]
     let coor2pin = vec![
@@ -332,17 +294,15 @@ fn should_crossover_if_a_left_b_right_have_overlapped_pins() {
         vec![None,    None,    None   ], // x=2
         vec![None,    Some(1), Some(2)], // x=3
     ];
-    let pin2coor = derive_pin2coor(&coor2pin, nx, ny, n_pin);
-    #[rustfmt::skip]
-    let b = Placement { problem: &problem, coor2pin, pin2coor, _cost:None };
+    let b = Placement::from_grid(&problem, coor2pin);
 
     let mut out = b.clone();
     crossover_half(&a, &b, &mut out, 2, &mut rand::thread_rng());
 
-    print_coor2pin(&problem, &out.coor2pin);
-    assert_eq!(&out.coor2pin[0][1], &Some(0));
-    assert_eq!(&out.coor2pin[3][1], &Some(1));
-    assert_eq!(&out.coor2pin[3][2], &Some(2));
+    print_coor2pin(&problem, &out.to_grid());
+    assert_eq!(out.coor2pin((0, 1)), Some(0));
+    assert_eq!(out.coor2pin((3, 1)), Some(1));
+    assert_eq!(out.coor2pin((3, 2)), Some(2));
 }
 
 #[allow(dead_code)]
@@ -356,32 +316,74 @@ fn print_coor2pin(problem: &Problem, coor2pin: &Vec<Vec<Option<PinID>>>) {
     }
 }
 
-fn print_stats(population: &mut Vec<Placement>) {
+#[derive(Clone)]
+pub struct GenerationStats {
+    pub best: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+pub struct GeneticResult {
+    pub best: Placement,
+    pub cost: f64,
+    pub elapsed: Duration,
+    pub n_generations: usize,
+    pub n_mutations: usize,
+    pub history: Vec<GenerationStats>,
+    pub stop_reason: StopReason,
+    // with `params.pareto`
+    pub pareto: Option<ParetoArchive>,
+    // of `best`, with `params.route`
+    pub routing: Option<RoutingResult>,
+}
+
+impl GeneticResult {
+    // see `ConsoleObserver` for the generations as they pass
+    pub fn print(&self) {
+        println!(
+            "best={:.0}   initial_best={:.0}   generations={}   mutations={}   time={:.2}s   stop={:?}",
+            self.cost,
+            self.history[0].best,
+            self.n_generations,
+            self.n_mutations,
+            self.elapsed.as_secs_f64(),
+            self.stop_reason
+        );
+    }
+}
+
+// of a population sorted by cost
+fn generation_stats(population: &mut [Placement]) -> GenerationStats {
     let best = population[0].cost_panic();
-    let mean = population
-        .iter_mut()
-        .map(|p| p.cost_mut() as f32)
-        .sum::<f32>()
-        / population.len() as f32;
+    let mean = population.iter_mut().map(|p| p.cost_mut()).sum::<f64>() / population.len() as f64;
     let variance = population
         .iter()
         .map(|p| {
-            let diff = mean - p.cost_panic() as f32;
+            let diff = mean - p.cost_panic();
             diff * diff
         })
-        .sum::<f32>()
-        / population.len() as f32;
-    let std = variance.sqrt();
+        .sum::<f64>()
+        / population.len() as f64;
+    GenerationStats {
+        best,
+        mean,
+        std_dev: variance.sqrt(),
+    }
+}
 
-    println!(
-        "best={best:6}   mean={mean:6.0}   std={std:4.0}",
-        best = best,
-        mean = mean,
-        std = std
-    );
+pub fn genetic_placement(problem: &Arc<Problem>, params: &Params) -> GeneticResult {
+    genetic_placement_with(problem, params, &mut |_: usize, _: &Step, _: &Placement| {
+        Control::Continue
+    })
 }
 
-pub fn genetic_placement(problem: &Problem, params: &Params) {
+// as `genetic_placement`, reporting every generation to `observer`
+pub fn genetic_placement_with(
+    problem: &Arc<Problem>,
+    params: &Params,
+    observer: &mut dyn Observer,
+) -> GeneticResult {
+    let start = Instant::now();
     // init population
     let mut population: Vec<Placement> = Vec::new();
     for _ in 0..params.n_population {
@@ -391,6 +393,16 @@ pub fn genetic_placement(problem: &Problem, params: &Params) {
     }
 
     let mut i_iter = 0;
+    let mut n_mutations = 0;
+    let mut history = Vec::new();
+    let mut archive = params
+        .pareto
+        .as_ref()
+        .map(|p| ParetoArchive::new(problem, p));
+    let mut limiter = params
+        .range_limit
+        .as_ref()
+        .map(|r| RangeLimiter::new(problem, r));
 
     let rng = &mut rand::thread_rng();
     loop {
@@ -398,13 +410,50 @@ pub fn genetic_placement(problem: &Problem, params: &Params) {
         population.iter_mut().for_each(|p| {
             p.cost_mut();
         });
-        population.sort_by_cached_key(|i| i.cost_panic());
-        if i_iter % 1000 == 0 {
-            print_stats(&mut population);
+        sort_by_cost(&mut population);
+        history.push(generation_stats(&mut population));
+        let control = observer.observe(
+            i_iter,
+            &Step::Generation(history.last().unwrap()),
+            &population[0],
+        );
+        let stop_reason = if control == Control::Stop {
+            Some(StopReason::Observer)
+        } else if i_iter > params.n_generation {
+            Some(StopReason::Completed)
+        } else {
+            params.budget.check(start, i_iter)
+        };
+        let done = stop_reason.is_some();
+        if let (Some(p), Some(a)) = (params.pareto.as_ref(), archive.as_mut()) {
+            if i_iter % p.every == 0 || done {
+                population.iter().for_each(|sol| {
+                    a.offer(sol);
+                });
+            }
         }
         // break if converge
-        if i_iter > params.n_generation {
-            break;
+        if done {
+            let stop_reason = stop_reason.unwrap();
+            let best = population.swap_remove(0);
+            observer.finish(i_iter, &Step::Generation(history.last().unwrap()), &best);
+            // a cancelled run returns at once, without the slow routing
+            let routing = params
+                .route
+                .as_ref()
+                .filter(|_| stop_reason != StopReason::Cancelled)
+                .map(|rp| route(&best, rp));
+            return GeneticResult {
+                cost: best.cost_panic(),
+                best,
+                elapsed: start.elapsed(),
+                n_generations: i_iter,
+                n_mutations,
+                history,
+                stop_reason,
+                routing,
+                pareto: archive,
+            };
         } else {
             i_iter += 1;
         }
@@ -436,9 +485,24 @@ pub fn genetic_placement(problem: &Problem, params: &Params) {
         // mutation
         let mut mutation_base = survived;
         mutation_base.extend(crossed.into_iter());
-        for i in 0..mutation_base.len() {
+        let window = limiter.as_ref().map(|l| l.window());
+        let (mut n_mutated, mut n_accepted) = (0, 0);
+        for sol in mutation_base.iter_mut() {
             if rng.gen::<f32>() < params.p_mutation {
-                mutate(&mut mutation_base[i]);
+                if limiter.is_some() {
+                    // the delta is only known if the cost is
+                    sol.cost_mut();
+                }
+                n_mutated += 1;
+                if mutate(sol, &params.moves, window) <= 0. {
+                    n_accepted += 1;
+                }
+            }
+        }
+        n_mutations += n_mutated;
+        if let Some(l) = limiter.as_mut() {
+            if n_mutated > 0 {
+                l.update(n_accepted as f64 / n_mutated as f64);
             }
         }
 
@@ -453,3 +517,29 @@ pub fn genetic_placement(problem: &Problem, params: &Params) {
         // }
     }
 }
+
+#[test]
+fn genetic_should_return_best_and_history() {
+    let problem = make_fixture();
+    #[rustfmt::skip]
+    let params = Params { n_generation: 50, n_population: 20, n_elite: 4, n_select: 10, n_crossover: 5, p_mutation: 1.0, moves: MoveParams::default(), range_limit: None, pareto: None, route: None, budget: Budget::default() };
+    let result = genetic_placement(&problem, &params);
+    assert_eq!(result.cost, result.best.cost_recompute());
+    assert_eq!(result.history.len(), result.n_generations + 1);
+    assert_eq!(result.history.last().unwrap().best, result.cost);
+    // the elite survive every generation
+    assert!(result.history.windows(2).all(|w| w[1].best <= w[0].best));
+}
+
+#[test]
+fn genetic_should_stop_at_its_generation_budget() {
+    let problem = make_fixture();
+    #[rustfmt::skip]
+    let mut params = Params { n_generation: 50, n_population: 20, n_elite: 4, n_select: 10, n_crossover: 5, p_mutation: 1.0, moves: MoveParams::default(), range_limit: None, pareto: None, route: None, budget: Budget::default() };
+    params.budget.max_steps = Some(5);
+    let result = genetic_placement(&problem, &params);
+    assert_eq!(result.stop_reason, StopReason::StepBudget);
+    assert_eq!(result.n_generations, 5);
+    assert!(result.best.validate().is_empty());
+    assert_eq!(result.cost, result.best.cost_recompute());
+}
@@ -1,9 +1,22 @@
 mod annealing;
+mod budget;
 mod genetic;
+mod moves;
+mod nsga2;
+mod observer;
+mod pareto;
 
 pub mod util;
 
-pub use annealing::annealing_placement;
 pub use annealing::Params as AnnealingParams;
-pub use genetic::genetic_placement;
+pub use annealing::Schedule;
+pub use annealing::{annealing_placement, annealing_placement_with};
+pub use budget::{Budget, CancelToken, StopReason};
 pub use genetic::Params as GeneticParams;
+pub use genetic::{genetic_placement, genetic_placement_with};
+pub use moves::{MoveParams, RangeParams};
+pub use nsga2::nsga2_placement;
+pub use nsga2::Params as Nsga2Params;
+pub use nsga2::SecondObjective;
+pub use observer::{ConsoleObserver, MemoryObserver, Throttled};
+pub use pareto::ParetoParams;
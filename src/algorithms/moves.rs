@@ -0,0 +1,467 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use super::util::{take_2, take_near};
+use crate::typing::{Coor, Placement, Problem};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveKind {
+    // exchange the contents of two random sites, either of which may be empty
+    Swap,
+    // move a cell to an empty site
+    Displace,
+    // cell a goes to the site of b, b to c and c to a
+    Rotate3,
+    // rotate the sites of a row or column segment by one
+    Shift,
+    // swap two equally sized rectangles of sites
+    SwapClusters,
+}
+
+// how often each kind of move is drawn, relative to the others
+#[derive(Clone)]
+pub struct MoveParams {
+    pub swap: f64,
+    pub displace: f64,
+    pub rotate3: f64,
+    pub shift: f64,
+    pub swap_clusters: f64,
+    // the longest segment shifted
+    pub max_segment: usize,
+    // the longest side of a swapped cluster
+    pub max_cluster: usize,
+}
+
+impl Default for MoveParams {
+    fn default() -> Self {
+        Self {
+            swap: 1.,
+            displace: 0.,
+            rotate3: 0.,
+            shift: 0.,
+            swap_clusters: 0.,
+            max_segment: 8,
+            max_cluster: 3,
+        }
+    }
+}
+
+impl MoveParams {
+    // comma-separated `kind=weight` pairs, e.g. "swap=1,displace=0.5";
+    // kinds left out are not drawn
+    pub fn parse(spec: &str) -> Self {
+        #[rustfmt::skip]
+        let mut params = Self { swap: 0., ..Self::default() };
+        for pair in spec.split(',') {
+            let (kind, weight) = pair
+                .split_once('=')
+                .unwrap_or_else(|| panic!("invalid move weight: {}", pair));
+            let weight: f64 = weight.parse().expect("invalid move weight");
+            match kind {
+                "swap" => params.swap = weight,
+                "displace" => params.displace = weight,
+                "rotate3" => params.rotate3 = weight,
+                "shift" => params.shift = weight,
+                "swap_clusters" => params.swap_clusters = weight,
+                _ => panic!("unknown move {}", kind),
+            }
+        }
+        params
+    }
+
+    fn draw_kind<R: Rng>(&self, rng: &mut R) -> MoveKind {
+        let kinds = [
+            (MoveKind::Swap, self.swap),
+            (MoveKind::Displace, self.displace),
+            (MoveKind::Rotate3, self.rotate3),
+            (MoveKind::Shift, self.shift),
+            (MoveKind::SwapClusters, self.swap_clusters),
+        ];
+        let total: f64 = kinds.iter().map(|(_, w)| w).sum();
+        let mut r = rng.gen::<f64>() * total;
+        for (kind, weight) in kinds.iter() {
+            if r < *weight {
+                return *kind;
+            }
+            r -= weight;
+        }
+        MoveKind::Swap
+    }
+}
+
+#[derive(Clone)]
+pub struct RangeParams {
+    // the fraction of moves the window is resized to get accepted
+    pub target_acceptance: f64,
+}
+
+impl Default for RangeParams {
+    fn default() -> Self {
+        Self {
+            target_acceptance: 0.44,
+        }
+    }
+}
+
+// VPR's range limiter: moves stay within a window around their first site,
+// which shrinks while too few moves are accepted and grows back when more
+// are, so that a cold placer stops trying long swaps it always rejects
+pub struct RangeLimiter {
+    window: f64,
+    max_window: f64,
+    target_acceptance: f64,
+}
+
+impl RangeLimiter {
+    pub fn new(problem: &Problem, params: &RangeParams) -> Self {
+        let max_window = problem.nx.max(problem.ny) as f64;
+        Self {
+            window: max_window,
+            max_window,
+            target_acceptance: params.target_acceptance,
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window.round() as usize
+    }
+
+    pub fn update(&mut self, acceptance: f64) {
+        self.window *= 1. - self.target_acceptance + acceptance;
+        self.window = self.window.clamp(1., self.max_window);
+    }
+}
+
+// a move as the site swaps it is made of. every swap undoes itself, so a
+// move is undone by replaying its swaps backwards, and its delta is the sum
+// of theirs
+pub struct Move {
+    pub kind: MoveKind,
+    pub swaps: Vec<(Coor, Coor)>,
+}
+
+impl Move {
+    pub fn new() -> Self {
+        Self {
+            kind: MoveKind::Swap,
+            swaps: Vec::new(),
+        }
+    }
+
+    pub fn random<R: Rng>(
+        sol: &Placement,
+        params: &MoveParams,
+        window: Option<usize>,
+        rng: &mut R,
+    ) -> Self {
+        let mut m = Self::new();
+        m.redraw(sol, params, window, rng);
+        m
+    }
+
+    // draw another move into this one, keeping its allocation. kinds that do
+    // not fit the placement, e.g. a displacement on a full chip, fall back to
+    // a plain swap. with a window, see `RangeLimiter`, every site but the
+    // first is drawn within it
+    pub fn redraw<R: Rng>(
+        &mut self,
+        sol: &Placement,
+        params: &MoveParams,
+        window: Option<usize>,
+        rng: &mut R,
+    ) {
+        self.swaps.clear();
+        self.kind = params.draw_kind(rng);
+        let drawn = match self.kind {
+            MoveKind::Swap => false,
+            MoveKind::Displace => self.draw_displace(sol, window, rng),
+            MoveKind::Rotate3 => self.draw_rotate3(sol, window, rng),
+            MoveKind::Shift => self.draw_shift(sol, params, rng),
+            MoveKind::SwapClusters => self.draw_swap_clusters(sol, params, window, rng),
+        };
+        if !drawn {
+            self.kind = MoveKind::Swap;
+            self.swaps.clear();
+            let problem = &sol.problem;
+            self.swaps.push(match window {
+                Some(w) => {
+                    let a = *problem.coors.choose(rng).unwrap();
+                    (a, take_near(a, w, problem.nx, problem.ny, rng))
+                }
+                None => take_2(&problem.coors),
+            });
+        }
+    }
+
+    fn draw_displace<R: Rng>(
+        &mut self,
+        sol: &Placement,
+        window: Option<usize>,
+        rng: &mut R,
+    ) -> bool {
+        let problem = &sol.problem;
+        if problem.n_pin == 0 || problem.n_pin >= problem.coors.len() {
+            return false;
+        }
+        let from = sol.pin2coor[rng.gen_range(0..problem.n_pin)];
+        let to = match window {
+            // the window may well be full
+            Some(w) => {
+                let free = (0..100)
+                    .map(|_| take_near(from, w, problem.nx, problem.ny, rng))
+                    .find(|coor| sol.coor2pin(*coor).is_none());
+                match free {
+                    Some(coor) => coor,
+                    None => return false,
+                }
+            }
+            None => loop {
+                let coor = *problem.coors.choose(rng).unwrap();
+                if sol.coor2pin(coor).is_none() {
+                    break coor;
+                }
+            },
+        };
+        self.swaps.push((from, to));
+        true
+    }
+
+    fn draw_rotate3<R: Rng>(
+        &mut self,
+        sol: &Placement,
+        window: Option<usize>,
+        rng: &mut R,
+    ) -> bool {
+        let problem = &sol.problem;
+        if problem.n_pin < 3 {
+            return false;
+        }
+        let (a, b, c) = match window {
+            // the other two sites may be empty, which rotates fewer cells
+            Some(w) => {
+                let a = sol.pin2coor[rng.gen_range(0..problem.n_pin)];
+                let b = take_near(a, w, problem.nx, problem.ny, rng);
+                let c = match (0..100)
+                    .map(|_| take_near(a, w, problem.nx, problem.ny, rng))
+                    .find(|c| *c != b)
+                {
+                    Some(c) => c,
+                    None => return false,
+                };
+                (a, b, c)
+            }
+            None => {
+                let pins = rand::seq::index::sample(rng, problem.n_pin, 3);
+                (
+                    sol.pin2coor[pins.index(0)],
+                    sol.pin2coor[pins.index(1)],
+                    sol.pin2coor[pins.index(2)],
+                )
+            }
+        };
+        self.swaps.push((a, b));
+        self.swaps.push((a, c));
+        true
+    }
+
+    fn draw_shift<R: Rng>(&mut self, sol: &Placement, params: &MoveParams, rng: &mut R) -> bool {
+        let (nx, ny) = (sol.problem.nx, sol.problem.ny);
+        let horizontal = rng.gen::<bool>();
+        let side = if horizontal { nx } else { ny };
+        let max_length = params.max_segment.min(side);
+        if max_length < 2 {
+            return false;
+        }
+        let length = rng.gen_range(2..=max_length);
+        let start = rng.gen_range(0..=side - length);
+        let line = rng.gen_range(0..if horizontal { ny } else { nx });
+        let site = |i: usize| {
+            let (i, line) = (i as u16, line as u16);
+            if horizontal {
+                (i, line)
+            } else {
+                (line, i)
+            }
+        };
+        for i in start..start + length - 1 {
+            self.swaps.push((site(i), site(i + 1)));
+        }
+        // carrying the first site to the end shifts the others back by one,
+        // carrying the last one to the start shifts them forward
+        if rng.gen::<bool>() {
+            self.swaps.reverse();
+        }
+        true
+    }
+
+    fn draw_swap_clusters<R: Rng>(
+        &mut self,
+        sol: &Placement,
+        params: &MoveParams,
+        window: Option<usize>,
+        rng: &mut R,
+    ) -> bool {
+        let (nx, ny) = (sol.problem.nx, sol.problem.ny);
+        if params.max_cluster == 0 || nx == 0 || ny == 0 {
+            return false;
+        }
+        let w = rng.gen_range(1..=params.max_cluster.min(nx));
+        let h = rng.gen_range(1..=params.max_cluster.min(ny));
+        // the corner of b within the window around the corner of a, leaving
+        // room for the cluster
+        let near = |a: usize, side: usize, size: usize| {
+            let r = window.unwrap_or(side).max(size);
+            a.saturating_sub(r)..=(a + r).min(side - size)
+        };
+        // clusters must not overlap; give up on chips too small to place two
+        for _ in 0..100 {
+            let a = (rng.gen_range(0..=nx - w), rng.gen_range(0..=ny - h));
+            let b = (
+                rng.gen_range(near(a.0, nx, w)),
+                rng.gen_range(near(a.1, ny, h)),
+            );
+            if a.0.abs_diff(b.0) < w && a.1.abs_diff(b.1) < h {
+                continue;
+            }
+            for dx in 0..w {
+                for dy in 0..h {
+                    let site = |c: (usize, usize)| ((c.0 + dx) as u16, (c.1 + dy) as u16);
+                    self.swaps.push((site(a), site(b)));
+                }
+            }
+            return true;
+        }
+        false
+    }
+
+    pub fn apply(&self, sol: &mut Placement) -> f64 {
+        self.apply_with(sol, |sol, ca, cb| sol.swap(ca, cb))
+    }
+
+    #[allow(dead_code)]
+    pub fn undo(&self, sol: &mut Placement) -> f64 {
+        self.undo_with(sol, |sol, ca, cb| sol.swap(ca, cb))
+    }
+
+    // apply the move one swap at a time through `swap`, which performs a
+    // swap and returns its delta, e.g. with extra cost terms on top
+    pub fn apply_with<F>(&self, sol: &mut Placement, mut swap: F) -> f64
+    where
+        F: FnMut(&mut Placement, Coor, Coor) -> f64,
+    {
+        self.swaps.iter().map(|(ca, cb)| swap(sol, *ca, *cb)).sum()
+    }
+
+    pub fn undo_with<F>(&self, sol: &mut Placement, mut swap: F) -> f64
+    where
+        F: FnMut(&mut Placement, Coor, Coor) -> f64,
+    {
+        self.swaps
+            .iter()
+            .rev()
+            .map(|(ca, cb)| swap(sol, *ca, *cb))
+            .sum()
+    }
+}
+
+#[test]
+fn every_move_should_undo_and_track_its_delta() {
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    let mut sol = problem.make_placement();
+    sol.cost_force();
+
+    let mut rng = rand::thread_rng();
+    #[rustfmt::skip]
+    let params = MoveParams { swap: 1., displace: 1., rotate3: 1., shift: 1., swap_clusters: 1., ..MoveParams::default() };
+    let mut seen = Vec::new();
+    for i in 0..500 {
+        let window = if i % 2 == 0 { None } else { Some(2) };
+        let m = Move::random(&sol, &params, window, &mut rng);
+        if !seen.contains(&m.kind) {
+            seen.push(m.kind);
+        }
+        let pin2coor = sol.pin2coor.clone();
+        let before = sol.cost_recompute();
+        let delta = m.apply(&mut sol);
+        assert!((sol.cost_recompute() - before - delta).abs() < 1e-6);
+        assert!(sol.validate().is_empty());
+
+        let undo = m.undo(&mut sol);
+        assert!((undo + delta).abs() < 1e-6);
+        assert_eq!(sol.pin2coor, pin2coor);
+    }
+    assert_eq!(seen.len(), 5);
+}
+
+#[test]
+fn moves_should_do_what_they_say() {
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    let mut sol = problem.make_placement();
+    let mut rng = rand::thread_rng();
+
+    #[rustfmt::skip]
+    let params = MoveParams { swap: 0., displace: 1., ..MoveParams::default() };
+    let m = Move::random(&sol, &params, None, &mut rng);
+    let (from, to) = m.swaps[0];
+    let pin = sol.coor2pin(from).unwrap();
+    assert_eq!(sol.coor2pin(to), None);
+    m.apply(&mut sol);
+    assert_eq!(sol.pin2coor[pin], to);
+
+    #[rustfmt::skip]
+    let params = MoveParams { swap: 0., rotate3: 1., ..MoveParams::default() };
+    let m = Move::random(&sol, &params, None, &mut rng);
+    let (a, b) = m.swaps[0];
+    let c = m.swaps[1].1;
+    let pins = [sol.coor2pin(a), sol.coor2pin(b), sol.coor2pin(c)];
+    m.apply(&mut sol);
+    assert_eq!([sol.coor2pin(b), sol.coor2pin(c), sol.coor2pin(a)], pins);
+
+    #[rustfmt::skip]
+    let params = MoveParams { swap: 0., shift: 1., ..MoveParams::default() };
+    let m = Move::random(&sol, &params, None, &mut rng);
+    let mut segment: Vec<Coor> = m.swaps.iter().flat_map(|s| vec![s.0, s.1]).collect();
+    segment.sort_unstable();
+    segment.dedup();
+    let mut before: Vec<_> = segment.iter().map(|c| sol.coor2pin(*c)).collect();
+    m.apply(&mut sol);
+    let after: Vec<_> = segment.iter().map(|c| sol.coor2pin(*c)).collect();
+    // one way or the other, every cell moves over by one
+    let mut back = before.clone();
+    back.rotate_left(1);
+    before.rotate_right(1);
+    assert!(after == back || after == before);
+}
+
+#[test]
+fn moves_should_stay_within_the_window() {
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    let sol = problem.make_placement();
+    let mut rng = rand::thread_rng();
+
+    #[rustfmt::skip]
+    let params = MoveParams { swap: 1., displace: 1., rotate3: 1., ..MoveParams::default() };
+    for _ in 0..500 {
+        let m = Move::random(&sol, &params, Some(3), &mut rng);
+        let first = m.swaps[0].0;
+        for (_, b) in m.swaps.iter() {
+            assert!(first.0.abs_diff(b.0) <= 3 && first.1.abs_diff(b.1) <= 3);
+        }
+    }
+
+    let mut limiter = RangeLimiter::new(&problem, &RangeParams::default());
+    assert_eq!(limiter.window(), 50);
+    limiter.update(0.9);
+    assert_eq!(limiter.window(), 50);
+    for _ in 0..20 {
+        limiter.update(0.1);
+    }
+    assert_eq!(limiter.window(), 1);
+    limiter.update(0.64);
+    assert_eq!(limiter.window(), 1);
+    limiter.update(1.);
+    assert_eq!(limiter.window(), 2);
+}
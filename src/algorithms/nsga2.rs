@@ -0,0 +1,250 @@
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::budget::{Budget, StopReason};
+use super::genetic::{crossover, mutate};
+use super::moves::MoveParams;
+use super::pareto::{crowding_distance, dominates};
+use crate::analysis::TimingGraph;
+use crate::typing::{BoundBox, Placement, Problem};
+
+// the objective minimized together with the wirelength
+pub enum SecondObjective {
+    // the largest half-perimeter of any net
+    MaxNetSpan,
+    CriticalDelay,
+}
+
+pub struct Params {
+    pub n_generation: usize,
+    pub n_population: usize,
+    pub p_mutation: f32,
+    pub moves: MoveParams,
+    pub second: SecondObjective,
+    // stop early after a time or a number of generations, or when cancelled
+    pub budget: Budget,
+}
+
+pub struct Nsga2Result {
+    // sorted by wirelength
+    pub front: Vec<(Vec<f64>, Placement)>,
+    pub stop_reason: StopReason,
+}
+
+impl Nsga2Result {
+    pub fn print(&self) {
+        for (values, _) in self.front.iter() {
+            println!("wirelength={:.0}   second={:.1}", values[0], values[1]);
+        }
+    }
+}
+
+// deb's fast non-dominated sort: the indices of every front, the first one
+// being dominated by nothing
+pub fn non_dominated_sort(values: &[Vec<f64>]) -> Vec<Vec<usize>> {
+    let n = values.len();
+    let mut dominated_by: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut n_dominating = vec![0; n];
+    for i in 0..n {
+        for j in 0..n {
+            if dominates(&values[i], &values[j]) {
+                dominated_by[i].push(j);
+            } else if dominates(&values[j], &values[i]) {
+                n_dominating[i] += 1;
+            }
+        }
+    }
+    let mut fronts = Vec::new();
+    let mut front: Vec<usize> = (0..n).filter(|i| n_dominating[*i] == 0).collect();
+    while !front.is_empty() {
+        let mut next = Vec::new();
+        for i in front.iter() {
+            for j in dominated_by[*i].iter() {
+                n_dominating[*j] -= 1;
+                if n_dominating[*j] == 0 {
+                    next.push(*j);
+                }
+            }
+        }
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+fn evaluate(sol: &mut Placement, second: &SecondObjective, graph: &TimingGraph) -> Vec<f64> {
+    let value = match second {
+        // global nets are routed apart, as everywhere else
+        SecondObjective::MaxNetSpan => sol
+            .problem
+            .nets
+            .iter()
+            .filter(|net| !net.is_global)
+            .map(|net| {
+                let mut bb = BoundBox::new();
+                for pin_id in &net.pins {
+                    bb.add_coor(sol.pin2coor[*pin_id]);
+                }
+                bb.half_perimeter()
+            })
+            .max()
+            .unwrap_or(0) as f64,
+        SecondObjective::CriticalDelay => {
+            let delay = sol.problem.delay_model.as_ref();
+            graph.analyze(sol, delay).critical_delay
+        }
+    };
+    vec![sol.cost_mut(), value]
+}
+
+// the front every individual is on and its crowding distance within it
+fn rank_and_crowding(values: &[Vec<f64>]) -> (Vec<usize>, Vec<f64>) {
+    let mut rank = vec![0; values.len()];
+    let mut crowding = vec![0.; values.len()];
+    for (r, front) in non_dominated_sort(values).iter().enumerate() {
+        let front_values: Vec<Vec<f64>> = front.iter().map(|i| values[*i].clone()).collect();
+        for (i, d) in front.iter().zip(crowding_distance(&front_values)) {
+            rank[*i] = r;
+            crowding[*i] = d;
+        }
+    }
+    (rank, crowding)
+}
+
+// NSGA-II: offspring from binary tournaments on (front, crowding), then the
+// best fronts of parents and offspring together survive, the last one that
+// fits only partly being cut by crowding distance. returns the final front
+pub fn nsga2_placement(problem: &Arc<Problem>, params: &Params) -> Nsga2Result {
+    let start = Instant::now();
+    let graph = TimingGraph::new(problem);
+    let n = params.n_population;
+    let mut population: Vec<(Vec<f64>, Placement)> = (0..n)
+        .map(|_| {
+            let mut sol = problem.make_placement();
+            (evaluate(&mut sol, &params.second, &graph), sol)
+        })
+        .collect();
+
+    let rng = &mut rand::thread_rng();
+    let mut stop_reason = StopReason::Completed;
+    for i_iter in 0..params.n_generation {
+        if let Some(reason) = params.budget.check(start, i_iter) {
+            stop_reason = reason;
+            break;
+        }
+        let values: Vec<Vec<f64>> = population.iter().map(|(v, _)| v.clone()).collect();
+        let (rank, crowding) = rank_and_crowding(&values);
+        let mut tournament = || {
+            let (a, b) = (rng.gen_range(0..n), rng.gen_range(0..n));
+            if rank[a] < rank[b] || (rank[a] == rank[b] && crowding[a] > crowding[b]) {
+                a
+            } else {
+                b
+            }
+        };
+
+        // offspring
+        let mut offspring = Vec::new();
+        while offspring.len() < n {
+            let (a, b) = (&population[tournament()].1, &population[tournament()].1);
+            let mut c = a.clone();
+            let mut d = b.clone();
+            c._cost = None;
+            d._cost = None;
+            crossover(a, b, &mut c, &mut d);
+            offspring.push(c);
+            offspring.push(d);
+        }
+        for mut sol in offspring.into_iter().take(n) {
+            if rng.gen::<f32>() < params.p_mutation {
+                mutate(&mut sol, &params.moves, None);
+            }
+            population.push((evaluate(&mut sol, &params.second, &graph), sol));
+        }
+
+        // environmental selection
+        let values: Vec<Vec<f64>> = population.iter().map(|(v, _)| v.clone()).collect();
+        let mut survivors: Vec<usize> = Vec::new();
+        for front in non_dominated_sort(&values) {
+            if survivors.len() + front.len() <= n {
+                survivors.extend(front);
+                continue;
+            }
+            let front_values: Vec<Vec<f64>> = front.iter().map(|i| values[*i].clone()).collect();
+            let distance = crowding_distance(&front_values);
+            let mut order: Vec<usize> = (0..front.len()).collect();
+            order.sort_by(|a, b| distance[*b].partial_cmp(&distance[*a]).unwrap());
+            survivors.extend(order.iter().take(n - survivors.len()).map(|k| front[*k]));
+            break;
+        }
+        let mut pool: Vec<Option<(Vec<f64>, Placement)>> =
+            population.into_iter().map(Some).collect();
+        population = survivors.iter().map(|i| pool[*i].take().unwrap()).collect();
+    }
+
+    // the first front without duplicated objective vectors
+    let values: Vec<Vec<f64>> = population.iter().map(|(v, _)| v.clone()).collect();
+    let mut first = non_dominated_sort(&values).swap_remove(0);
+    first.sort_by(|a, b| values[*a].partial_cmp(&values[*b]).unwrap());
+    first.dedup_by(|a, b| values[*a] == values[*b]);
+    let mut pool: Vec<Option<(Vec<f64>, Placement)>> = population.into_iter().map(Some).collect();
+    let front: Vec<(Vec<f64>, Placement)> =
+        first.iter().map(|i| pool[*i].take().unwrap()).collect();
+    Nsga2Result { front, stop_reason }
+}
+
+#[test]
+fn should_sort_into_fronts() {
+    #[rustfmt::skip]
+    let values = vec![
+        vec![1., 4.], vec![2., 2.], vec![4., 1.], // front 0
+        vec![3., 3.], vec![2., 5.],               // front 1
+        vec![5., 5.],                             // front 2
+    ];
+    let fronts = non_dominated_sort(&values);
+    assert_eq!(fronts, vec![vec![0, 1, 2], vec![3, 4], vec![5]]);
+}
+
+#[test]
+fn nsga2_should_return_non_dominated_front() {
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    #[rustfmt::skip]
+    let params = Params { n_generation: 20, n_population: 20, p_mutation: 1.0, moves: MoveParams::default(), second: SecondObjective::CriticalDelay, budget: Budget::default() };
+    let result = nsga2_placement(&problem, &params);
+    assert_eq!(result.stop_reason, StopReason::Completed);
+    let front = result.front;
+    assert!(!front.is_empty());
+    for (a, _) in front.iter() {
+        assert!(front.iter().all(|(b, _)| !dominates(b, a)));
+    }
+    for (values, sol) in front.iter() {
+        assert_eq!(values[0], sol.cost_recompute());
+    }
+}
+
+#[test]
+fn max_net_span_should_skip_global_nets() {
+    use crate::typing::Net;
+    #[rustfmt::skip]
+    let nets = vec![
+        Net::new(0, "a", vec![0, 1]),
+        Net {is_global: true, ..Net::new(1, "clk", vec![0, 2])},
+    ];
+    let problem = Arc::new(Problem::from_nets(4, 3, 3, nets));
+    #[rustfmt::skip]
+    let coor2pin = vec![
+        //   y=0      y=1      y=2
+        vec![Some(0), Some(1), None   ], // x=0
+        vec![None,    None,    None   ], // x=1
+        vec![None,    None,    None   ], // x=2
+        vec![None,    None,    Some(2)], // x=3
+    ];
+    let mut sol = Placement::from_grid(&problem, coor2pin);
+    let graph = TimingGraph::new(&problem);
+    assert_eq!(
+        evaluate(&mut sol, &SecondObjective::MaxNetSpan, &graph)[1],
+        1.
+    );
+}
@@ -0,0 +1,190 @@
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant};
+
+use super::annealing::TemperatureStats;
+use super::genetic::GenerationStats;
+use crate::typing::Placement;
+
+// what a placer has just finished
+pub enum Step<'a> {
+    Temperature(&'a TemperatureStats),
+    Generation(&'a GenerationStats),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Continue,
+    // finish the run early, returning the best placement so far
+    Stop,
+}
+
+// watches a run as it goes: placers call it after every temperature or
+// generation `i`, with the best placement so far
+pub trait Observer {
+    fn observe(&mut self, i: usize, step: &Step, best: &Placement) -> Control;
+
+    // called once the run ends, with the last step `observe` was given
+    fn finish(&mut self, _i: usize, _step: &Step, _best: &Placement) {}
+}
+
+// a closure observes too, e.g. to stop at a target cost
+impl<F> Observer for F
+where
+    F: FnMut(usize, &Step, &Placement) -> Control,
+{
+    fn observe(&mut self, i: usize, step: &Step, best: &Placement) -> Control {
+        self(i, step, best)
+    }
+}
+
+// one line per step
+pub struct ConsoleObserver;
+
+impl Observer for ConsoleObserver {
+    fn observe(&mut self, i: usize, step: &Step, _best: &Placement) -> Control {
+        match step {
+            Step::Temperature(s) => println!(
+                "i={:3}   t={:.2}   acceptance={:.2}   cost={:5.0}   std={:.1}",
+                i, s.t, s.acceptance, s.cost, s.std_dev
+            ),
+            Step::Generation(s) => println!(
+                "i={:5}   best={:6.0}   mean={:6.0}   std={:4.0}",
+                i, s.best, s.mean, s.std_dev
+            ),
+        }
+        Control::Continue
+    }
+}
+
+// every step and the cost of the best placement after it
+#[derive(Default)]
+pub struct MemoryObserver {
+    pub temperatures: Vec<TemperatureStats>,
+    pub generations: Vec<GenerationStats>,
+    pub best_costs: Vec<f64>,
+}
+
+impl MemoryObserver {
+    // one row per step, the cost of the best placement last
+    pub fn write_csv(&self, path: &str) -> io::Result<()> {
+        let mut content = String::new();
+        if !self.temperatures.is_empty() {
+            content.push_str("i,t,acceptance,cost,std_dev,best\n");
+        }
+        for (i, (s, best)) in self
+            .temperatures
+            .iter()
+            .zip(self.best_costs.iter())
+            .enumerate()
+        {
+            content.push_str(&format!(
+                "{},{},{:.4},{:.3},{:.3},{:.3}\n",
+                i, s.t, s.acceptance, s.cost, s.std_dev, best
+            ));
+        }
+        if !self.generations.is_empty() {
+            content.push_str("i,best,mean,std_dev\n");
+        }
+        for (i, s) in self.generations.iter().enumerate() {
+            content.push_str(&format!(
+                "{},{:.3},{:.3},{:.3}\n",
+                i, s.best, s.mean, s.std_dev
+            ));
+        }
+        fs::write(path, content)
+    }
+}
+
+impl Observer for MemoryObserver {
+    fn observe(&mut self, _i: usize, step: &Step, best: &Placement) -> Control {
+        match step {
+            Step::Temperature(s) => self.temperatures.push((*s).clone()),
+            Step::Generation(s) => self.generations.push((*s).clone()),
+        }
+        self.best_costs.push(best.cost_panic());
+        Control::Continue
+    }
+}
+
+// passes at most one step per `interval` on to `inner`, the first and the
+// last included
+pub struct Throttled<O> {
+    pub inner: O,
+    pub interval: Duration,
+    last: Option<Instant>,
+    // whether the latest step was held back
+    skipped: bool,
+}
+
+impl<O: Observer> Throttled<O> {
+    pub fn new(inner: O, interval: Duration) -> Self {
+        Self {
+            inner,
+            interval,
+            last: None,
+            skipped: false,
+        }
+    }
+}
+
+impl<O: Observer> Observer for Throttled<O> {
+    fn observe(&mut self, i: usize, step: &Step, best: &Placement) -> Control {
+        self.skipped = self.last.is_some_and(|last| last.elapsed() < self.interval);
+        if self.skipped {
+            return Control::Continue;
+        }
+        self.last = Some(Instant::now());
+        self.inner.observe(i, step, best)
+    }
+
+    fn finish(&mut self, i: usize, step: &Step, best: &Placement) {
+        // the run is over, whatever `inner` answers
+        if self.skipped {
+            self.inner.observe(i, step, best);
+        }
+        self.inner.finish(i, step, best);
+    }
+}
+
+#[test]
+fn throttled_should_pass_on_the_first_and_last_steps_only_within_interval() {
+    use crate::typing::Problem;
+    use std::sync::Arc;
+    let problem = Arc::new(Problem::from_nets(4, 3, 3, vec![]));
+    let mut sol = problem.make_placement();
+    sol.cost_force();
+    #[rustfmt::skip]
+    let stats = GenerationStats { best: 1., mean: 2., std_dev: 0.5 };
+
+    let mut throttled = Throttled::new(MemoryObserver::default(), Duration::from_secs(3600));
+    for i in 0..10 {
+        throttled.observe(i, &Step::Generation(&stats), &sol);
+    }
+    assert_eq!(throttled.inner.generations.len(), 1);
+    throttled.finish(9, &Step::Generation(&stats), &sol);
+    assert_eq!(throttled.inner.generations.len(), 2);
+    assert_eq!(throttled.inner.best_costs, vec![0., 0.]);
+
+    // a last step already passed on is not repeated
+    let mut throttled = Throttled::new(MemoryObserver::default(), Duration::from_secs(3600));
+    throttled.observe(0, &Step::Generation(&stats), &sol);
+    throttled.finish(0, &Step::Generation(&stats), &sol);
+    assert_eq!(throttled.inner.generations.len(), 1);
+
+    let mut n_seen = 0;
+    let mut stop_at_three = |i: usize, _: &Step, _: &Placement| {
+        n_seen += 1;
+        if i == 3 {
+            Control::Stop
+        } else {
+            Control::Continue
+        }
+    };
+    let mut throttled = Throttled::new(&mut stop_at_three, Duration::ZERO);
+    let controls: Vec<Control> = (0..5)
+        .map(|i| throttled.observe(i, &Step::Generation(&stats), &sol))
+        .collect();
+    assert_eq!(controls[3], Control::Stop);
+    assert_eq!(n_seen, 5);
+}
@@ -0,0 +1,206 @@
+use std::fs;
+use std::io;
+
+use crate::analysis::{CongestionMap, TimingGraph};
+use crate::typing::{Placement, Problem};
+
+#[derive(Clone)]
+pub struct ParetoParams {
+    // most placements kept; the most crowded ones are dropped beyond it
+    pub capacity: usize,
+    // temperatures (annealing) or generations (genetic) between two updates
+    pub every: usize,
+    // routing demand a tile can take before it overflows
+    pub congestion_capacity: f64,
+    // directory to write the front and its placements to, if anywhere
+    pub export_dir: Option<String>,
+}
+
+impl Default for ParetoParams {
+    fn default() -> Self {
+        Self {
+            capacity: 50,
+            every: 1,
+            congestion_capacity: 2.,
+            export_dir: None,
+        }
+    }
+}
+
+// every objective is minimized
+pub fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b.iter()).all(|(x, y)| x <= y) && a.iter().zip(b.iter()).any(|(x, y)| x < y)
+}
+
+// NSGA-II crowding distance: for every point, the sum over objectives of the
+// normalized gap between its two neighbours; the extremes are infinite
+pub fn crowding_distance(values: &[Vec<f64>]) -> Vec<f64> {
+    let n = values.len();
+    let mut distance = vec![0.; n];
+    if n == 0 {
+        return distance;
+    }
+    let n_objective = values[0].len();
+    for k in 0..n_objective {
+        let column: Vec<f64> = values.iter().map(|v| v[k]).collect();
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|a, b| column[*a].partial_cmp(&column[*b]).unwrap());
+        let (first, last) = (order[0], order[n - 1]);
+        let range = column[last] - column[first];
+        // an objective everyone agrees on says nothing about crowding
+        if range <= 0. {
+            continue;
+        }
+        distance[first] = f64::INFINITY;
+        distance[last] = f64::INFINITY;
+        for i in 1..n - 1 {
+            distance[order[i]] += (column[order[i + 1]] - column[order[i - 1]]) / range;
+        }
+    }
+    distance
+}
+
+// wirelength under the cost model, critical path delay and RUDY overflow
+pub struct Objectives {
+    graph: TimingGraph,
+    congestion_capacity: f64,
+}
+
+impl Objectives {
+    pub const NAMES: [&'static str; 3] = ["wirelength", "critical_delay", "overflow"];
+
+    pub fn new(problem: &Problem, congestion_capacity: f64) -> Self {
+        Self {
+            graph: TimingGraph::new(problem),
+            congestion_capacity,
+        }
+    }
+
+    pub fn evaluate(&self, sol: &Placement) -> Vec<f64> {
+        let report = self.graph.analyze(sol, sol.problem.delay_model.as_ref());
+        let map = CongestionMap::from_placement(sol, self.congestion_capacity);
+        vec![sol.cost_recompute(), report.critical_delay, map.overflow()]
+    }
+}
+
+// the non-dominated placements seen during a run, with their objectives
+pub struct ParetoArchive {
+    pub objectives: Objectives,
+    pub entries: Vec<(Vec<f64>, Placement)>,
+    capacity: usize,
+}
+
+impl ParetoArchive {
+    pub fn new(problem: &Problem, params: &ParetoParams) -> Self {
+        if params.every == 0 {
+            panic!("pareto updates have to be at least 1 step apart");
+        }
+        Self {
+            objectives: Objectives::new(problem, params.congestion_capacity),
+            entries: Vec::new(),
+            capacity: params.capacity,
+        }
+    }
+
+    // evaluate a placement and keep a copy of it if nothing in the archive
+    // dominates it. returns whether it was kept
+    pub fn offer(&mut self, sol: &Placement) -> bool {
+        let values = self.objectives.evaluate(sol);
+        self.insert(values, sol)
+    }
+
+    pub fn insert(&mut self, values: Vec<f64>, sol: &Placement) -> bool {
+        if self
+            .entries
+            .iter()
+            .any(|(v, _)| dominates(v, &values) || *v == values)
+        {
+            return false;
+        }
+        self.entries.retain(|(v, _)| !dominates(&values, v));
+        self.entries.push((values, sol.clone()));
+        if self.entries.len() > self.capacity {
+            let values: Vec<Vec<f64>> = self.entries.iter().map(|(v, _)| v.clone()).collect();
+            let distance = crowding_distance(&values);
+            let crowded = (0..distance.len())
+                .min_by(|a, b| distance[*a].partial_cmp(&distance[*b]).unwrap())
+                .unwrap();
+            self.entries.swap_remove(crowded);
+            return crowded != self.entries.len();
+        }
+        true
+    }
+
+    // front.csv lists the objectives of every placement, placement_<i>.csv
+    // the site of every cell of placement i. files of an earlier export to
+    // the same directory are removed first
+    pub fn export(&self, dir: &str) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if name == "front.csv" || name.starts_with("placement_") && name.ends_with(".csv") {
+                fs::remove_file(&path)?;
+            }
+        }
+        let mut front = format!("id,{}\n", Objectives::NAMES.join(","));
+        for (i, (values, sol)) in self.entries.iter().enumerate() {
+            let values: Vec<String> = values.iter().map(|v| format!("{:.3}", v)).collect();
+            front.push_str(&format!("{},{}\n", i, values.join(",")));
+
+            let mut cells = String::from("cell,x,y\n");
+            for (cell, (x, y)) in sol.pin2coor.iter().enumerate() {
+                cells.push_str(&format!("{},{},{}\n", cell, x, y));
+            }
+            fs::write(format!("{}/placement_{}.csv", dir, i), cells)?;
+        }
+        fs::write(format!("{}/front.csv", dir), front)
+    }
+
+    // print the front and export it if asked to
+    pub fn report(&self, params: &ParetoParams) {
+        self.print();
+        if let Some(dir) = &params.export_dir {
+            self.export(dir).expect("cannot export pareto front");
+        }
+    }
+
+    pub fn print(&self) {
+        println!("pareto front: {} placements", self.entries.len());
+        let mut entries: Vec<&Vec<f64>> = self.entries.iter().map(|(v, _)| v).collect();
+        entries.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+        for values in entries {
+            let columns: Vec<String> = Objectives::NAMES
+                .iter()
+                .zip(values.iter())
+                .map(|(name, v)| format!("{}={:.1}", name, v))
+                .collect();
+            println!("{}", columns.join("   "));
+        }
+    }
+}
+
+#[test]
+fn archive_should_keep_non_dominated_placements() {
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    let sol = problem.make_placement();
+
+    #[rustfmt::skip]
+    let params = ParetoParams { capacity: 3, ..ParetoParams::default() };
+    let mut archive = ParetoArchive::new(&problem, &params);
+    assert!(archive.insert(vec![1., 5., 0.], &sol));
+    assert!(archive.insert(vec![5., 1., 0.], &sol));
+    assert!(!archive.insert(vec![6., 6., 0.], &sol));
+    assert!(archive.insert(vec![3., 3., 0.], &sol));
+    assert_eq!(archive.entries.len(), 3);
+
+    // over capacity the most crowded trade-off is dropped
+    assert!(!archive.insert(vec![2., 4., 0.], &sol));
+    assert_eq!(archive.entries.len(), 3);
+    assert!(archive.entries.iter().all(|(v, _)| v[0] != 2.));
+
+    // a placement better in everything replaces the whole front
+    assert!(archive.insert(vec![0., 0., 0.], &sol));
+    assert_eq!(archive.entries.len(), 1);
+}
@@ -1,11 +1,50 @@
-use rand::seq::SliceRandom;
+use rand::Rng;
 
-pub fn take_2<T>(v: &Vec<T>) -> (T, T)
+use crate::analysis::{DelayModel, TimingGraph};
+use crate::cost::{Hpwl, Steiner};
+use crate::typing::{Coor, Placement};
+
+// two distinct elements, drawn without allocating as it runs once per move
+pub fn take_2<T>(v: &[T]) -> (T, T)
 where
     T: Copy,
 {
-    let ab = v
-        .choose_multiple(&mut rand::thread_rng(), 2)
-        .collect::<Vec<_>>();
-    (*ab[0], *ab[1])
+    let mut rng = rand::thread_rng();
+    let a = rng.gen_range(0..v.len());
+    let mut b = rng.gen_range(0..v.len() - 1);
+    if b >= a {
+        b += 1;
+    }
+    (v[a], v[b])
+}
+
+// a site other than `a`, at most `window` sites away from it in x and y
+pub fn take_near<R: Rng>(a: Coor, window: usize, nx: usize, ny: usize, rng: &mut R) -> Coor {
+    assert!(nx * ny > 1 && window > 0, "no site near {:?}", a);
+    let (x, y) = (a.0 as usize, a.1 as usize);
+    let xs = x.saturating_sub(window)..=(x + window).min(nx - 1);
+    let ys = y.saturating_sub(window)..=(y + window).min(ny - 1);
+    loop {
+        let b = (
+            rng.gen_range(xs.clone()) as u16,
+            rng.gen_range(ys.clone()) as u16,
+        );
+        if b != a {
+            return b;
+        }
+    }
+}
+
+// the optimized cost next to wirelength estimates that are independent of
+// the cost model, to see whether the optimization tracks routed wirelength
+pub fn print_report(sol: &Placement, delay: &dyn DelayModel) {
+    println!(
+        "{}={:.0}   hpwl={:.0}   steiner={:.0}",
+        sol.problem.cost_model.name(),
+        sol.cost_panic(),
+        sol.cost_with(&Hpwl),
+        sol.cost_with(&Steiner)
+    );
+    let graph = TimingGraph::new(&sol.problem);
+    graph.analyze(sol, delay).print(5);
 }
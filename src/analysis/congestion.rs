@@ -0,0 +1,220 @@
+use std::fs;
+use std::io;
+
+use crate::typing::{BoundBox, Coor, Journal, Placement};
+
+// RUDY: every net spreads its expected wire demand, the half-perimeter of
+// its box, uniformly over the tiles its bounding box covers
+pub struct CongestionMap {
+    pub nx: usize,
+    pub ny: usize,
+    pub capacity: f64,
+    pub demand: Vec<f64>,
+    overflow: f64,
+    // the demand and overflow before the open move
+    journal: Journal<f64>,
+    overflow_before: f64,
+}
+
+impl CongestionMap {
+    pub fn new(nx: usize, ny: usize, capacity: f64) -> Self {
+        Self {
+            nx,
+            ny,
+            capacity,
+            demand: vec![0.; nx * ny],
+            overflow: 0.,
+            journal: Journal::new(),
+            overflow_before: 0.,
+        }
+    }
+
+    pub fn from_placement(sol: &Placement, capacity: f64) -> Self {
+        let problem = &sol.problem;
+        let mut map = Self::new(problem.nx, problem.ny, capacity);
+        for net in problem.nets.iter().filter(|net| !net.is_global) {
+            let mut bb = BoundBox::new();
+            for pin_id in &net.pins {
+                bb.add_coor(sol.pin2coor[*pin_id]);
+            }
+            map.add_net(&bb, 1.);
+        }
+        map
+    }
+
+    pub fn demand_at(&self, coor: Coor) -> f64 {
+        self.demand[coor.0 as usize * self.ny + coor.1 as usize]
+    }
+
+    // add (scale = 1) or remove (scale = -1) the demand of a net
+    pub fn add_net(&mut self, bb: &BoundBox, scale: f64) {
+        let w = (bb.right - bb.left + 1) as f64;
+        let h = (bb.bottom - bb.top + 1) as f64;
+        let density = scale * (w + h) / (w * h);
+        for x in bb.left..=bb.right {
+            for y in bb.top..=bb.bottom {
+                self.journal
+                    .record(x * self.ny + y, &self.demand[x * self.ny + y]);
+                let d = &mut self.demand[x * self.ny + y];
+                let before = (*d - self.capacity).max(0.);
+                *d += density;
+                self.overflow += (*d - self.capacity).max(0.) - before;
+            }
+        }
+    }
+
+    // from here on `add_net` journals the tiles it touches, so that a
+    // rollback restores their demand and the overflow
+    pub fn begin_move(&mut self) {
+        self.journal.begin();
+        self.overflow_before = self.overflow;
+    }
+
+    pub fn commit(&mut self) {
+        self.journal.commit();
+    }
+
+    pub fn rollback(&mut self) {
+        self.journal.rollback(&mut self.demand);
+        self.overflow = self.overflow_before;
+    }
+
+    // demand above capacity, summed over all tiles
+    pub fn overflow(&self) -> f64 {
+        self.overflow
+    }
+
+    pub fn max_demand(&self) -> f64 {
+        self.demand.iter().cloned().fold(0., f64::max)
+    }
+
+    // one row per y, one column per x
+    pub fn write_csv(&self, path: &str) -> io::Result<()> {
+        let mut content = String::new();
+        for y in 0..self.ny {
+            let row: Vec<String> = (0..self.nx)
+                .map(|x| format!("{:.3}", self.demand_at((x as u16, y as u16)).max(0.)))
+                .collect();
+            content.push_str(&row.join(","));
+            content.push('\n');
+        }
+        fs::write(path, content)
+    }
+}
+
+pub struct CongestionParams {
+    pub weight: f64,
+    // routing demand a tile can take before it overflows
+    pub capacity: f64,
+    // where to write the final map, if anywhere
+    pub export_path: Option<String>,
+}
+
+// the congestion term of the annealer: weight * overflow, kept up to date
+// from the net boxes the placement maintains
+pub struct CongestionCost {
+    pub map: CongestionMap,
+    pub weight: f64,
+    nets: Vec<usize>,
+    saved: Vec<BoundBox>,
+}
+
+impl CongestionCost {
+    pub fn new(sol: &Placement, params: &CongestionParams) -> Self {
+        Self {
+            map: CongestionMap::from_placement(sol, params.capacity),
+            weight: params.weight,
+            nets: Vec::new(),
+            saved: Vec::new(),
+        }
+    }
+
+    pub fn cost(&self) -> f64 {
+        self.weight * self.map.overflow()
+    }
+
+    // remember the boxes of the nets a swap of `ca` and `cb` will touch
+    pub fn before_swap(&mut self, sol: &Placement, ca: Coor, cb: Coor) {
+        self.nets = sol.nets_at(&[ca, cb]);
+        self.nets.retain(|n| !sol.problem.nets[*n].is_global);
+        self.saved = self
+            .nets
+            .iter()
+            .map(|n| sol.bound_box(*n).clone())
+            .collect();
+    }
+
+    // move the demand of the remembered nets to their current boxes,
+    // returning the change in overflow
+    pub fn after_swap(&mut self, sol: &Placement) -> f64 {
+        let before = self.map.overflow();
+        for (net_id, saved) in self.nets.iter().zip(self.saved.iter_mut()) {
+            let bb = sol.bound_box(*net_id);
+            self.map.add_net(saved, -1.);
+            self.map.add_net(bb, 1.);
+            *saved = bb.clone();
+        }
+        self.weight * (self.map.overflow() - before)
+    }
+
+    pub fn begin_move(&mut self) {
+        self.map.begin_move();
+    }
+
+    pub fn commit(&mut self) {
+        self.map.commit();
+    }
+
+    pub fn rollback(&mut self) {
+        self.map.rollback();
+    }
+}
+
+#[test]
+fn rudy_should_spread_wire_demand_over_bound_box() {
+    let mut map = CongestionMap::new(4, 3, 0.5);
+    let mut bb = BoundBox::new();
+    bb.add_coor((0, 0));
+    bb.add_coor((1, 2));
+    map.add_net(&bb, 1.);
+
+    // 2 x 3 tiles share a demand of 2 + 3
+    assert!((map.demand_at((1, 1)) - 5. / 6.).abs() < 1e-9);
+    assert_eq!(map.demand_at((2, 1)), 0.);
+    let total: f64 = map.demand.iter().sum();
+    assert!((total - 5.).abs() < 1e-9);
+    assert!((map.overflow() - 6. * (5. / 6. - 0.5)).abs() < 1e-9);
+
+    map.add_net(&bb, -1.);
+    assert!(map.overflow().abs() < 1e-9);
+}
+
+#[test]
+fn incremental_overflow_should_match_rebuilt_map() {
+    use crate::algorithms::util::take_2;
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    let mut sol = problem.make_placement();
+    sol.cost_force();
+
+    #[rustfmt::skip]
+    let params = CongestionParams { weight: 1., capacity: 0.5, export_path: None };
+    let mut c = CongestionCost::new(&sol, &params);
+    for i in 0..2_000 {
+        let (ca, cb) = take_2(&problem.coors);
+        sol.begin_move();
+        c.begin_move();
+        c.before_swap(&sol, ca, cb);
+        sol.swap(ca, cb);
+        c.after_swap(&sol);
+        if i % 2 == 0 {
+            sol.rollback();
+            c.rollback();
+        } else {
+            sol.commit();
+            c.commit();
+        }
+    }
+    let rebuilt = CongestionMap::from_placement(&sol, 0.5);
+    assert!((c.map.overflow() - rebuilt.overflow()).abs() < 1e-6);
+}
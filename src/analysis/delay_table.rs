@@ -0,0 +1,101 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use super::DelayModel;
+use crate::typing::{Architecture, Coor};
+
+// connection delays for every (dx, dy), found once per architecture by
+// shortest paths over a routing-resource graph whose nodes are tiles and
+// whose edges are the wire segments leaving a tile in each direction
+pub struct DelayTable {
+    pub lut: f64,
+    ny: usize,
+    table: Vec<f64>,
+}
+
+// a node and its distance, ordered for a min-heap
+#[derive(PartialEq)]
+pub struct State(pub f64, pub usize);
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // min-heap on delay
+        other.0.partial_cmp(&self.0).unwrap()
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl DelayTable {
+    pub fn new(arch: &Architecture, nx: usize, ny: usize) -> Self {
+        // the grid is translation invariant, so routing from the corner
+        // reaches every offset a connection can have
+        let mut dist = vec![f64::INFINITY; nx * ny];
+        let mut heap = BinaryHeap::new();
+        dist[0] = 0.;
+        heap.push(State(0., 0));
+        while let Some(State(d, node)) = heap.pop() {
+            if d > dist[node] {
+                continue;
+            }
+            let (x, y) = (node / ny, node % ny);
+            for seg in arch.segments.iter() {
+                let cost = d + arch.switch_delay + seg.delay;
+                let targets = [
+                    (x.saturating_sub(seg.length), y),
+                    ((x + seg.length).min(nx - 1), y),
+                    (x, y.saturating_sub(seg.length)),
+                    (x, (y + seg.length).min(ny - 1)),
+                ];
+                for (tx, ty) in targets {
+                    let next = tx * ny + ty;
+                    if cost < dist[next] {
+                        dist[next] = cost;
+                        heap.push(State(cost, next));
+                    }
+                }
+            }
+        }
+
+        let pins = arch.opin_delay + arch.ipin_delay;
+        Self {
+            lut: arch.lut_delay,
+            ny,
+            table: dist.iter().map(|d| d + pins).collect(),
+        }
+    }
+}
+
+impl DelayModel for DelayTable {
+    fn lut_delay(&self) -> f64 {
+        self.lut
+    }
+
+    fn net_delay(&self, from: Coor, to: Coor) -> f64 {
+        let dx = from.0.abs_diff(to.0) as usize;
+        let dy = from.1.abs_diff(to.1) as usize;
+        self.table[dx * self.ny + dy]
+    }
+}
+
+#[test]
+fn delay_table_should_prefer_long_segments() {
+    let arch = Architecture::from_file("benchmarks/simple.arch");
+    let table = DelayTable::new(&arch, 10, 10);
+    let pins = 0.2;
+    // a single length-1 wire
+    assert!((table.net_delay((3, 3), (4, 3)) - (pins + 0.2)).abs() < 1e-9);
+    // one length-4 wire beats four length-1 wires
+    assert!((table.net_delay((0, 0), (0, 4)) - (pins + 0.35)).abs() < 1e-9);
+    // symmetric in direction
+    assert_eq!(
+        table.net_delay((5, 2), (1, 7)),
+        table.net_delay((1, 7), (5, 2))
+    );
+}
@@ -0,0 +1,13 @@
+mod congestion;
+mod delay_table;
+mod power;
+mod routing;
+mod timing;
+
+pub use congestion::{CongestionCost, CongestionMap, CongestionParams};
+pub use delay_table::DelayTable;
+pub use power::{
+    estimate_activities, interconnect_power, read_activity_file, Activity, PowerCost, PowerParams,
+};
+pub use routing::{min_channel_width, route, RouterParams, RoutingResult};
+pub use timing::{DelayModel, LinearDelay, TimingCost, TimingGraph, TimingParams};
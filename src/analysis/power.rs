@@ -0,0 +1,355 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use crate::cost::cross_count;
+use crate::typing::{BLIFInfo, BoundBox, Coor, Cover, Journal, Placement};
+
+// the probability of a signal being 1 and its expected number of
+// transitions per clock cycle
+#[derive(Debug, Clone, Copy)]
+pub struct Activity {
+    pub probability: f64,
+    pub density: f64,
+}
+
+// random primary inputs toggle every other cycle
+pub const INPUT_ACTIVITY: Activity = Activity {
+    probability: 0.5,
+    density: 0.5,
+};
+
+// one `net probability density` triple per line, as in VPR's .act files
+pub fn read_activity_file(path: &str) -> HashMap<String, Activity> {
+    let content = fs::read_to_string(path).expect("cannot read file");
+    let mut activities = HashMap::new();
+    for line in content.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            [] => {}
+            [net, probability, density] => {
+                let activity = Activity {
+                    probability: probability.parse().expect("invalid probability"),
+                    density: density.parse().expect("invalid density"),
+                };
+                activities.insert(net.to_string(), activity);
+            }
+            _ => panic!("invalid activity line: {}", line),
+        }
+    }
+    activities
+}
+
+// propagate activities from the primary inputs through the cell covers,
+// assuming independent inputs: the output probability sums the minterms the
+// cover is true for, and the output density is Najm's transition density,
+// the sum over inputs of P(boolean difference) * input density. nets listed
+// in `fixed` keep the given activity. a combinational loop is cut where it
+// closes, and that input as well as the output of a cover too wide to
+// enumerate get the primary input activity
+pub fn estimate_activities(
+    blif: &BLIFInfo,
+    fixed: &HashMap<String, Activity>,
+) -> HashMap<String, Activity> {
+    let mut activities = fixed.clone();
+    for net in blif.net_list.keys() {
+        propagate(blif, net, &mut activities);
+    }
+    activities
+}
+
+// covers with more inputs than this are not enumerated
+const MAX_COVER_INPUTS: usize = 20;
+
+// evaluate `net` after its transitive fanin, depth first without recursion
+fn propagate<'b>(blif: &'b BLIFInfo, net: &'b str, activities: &mut HashMap<String, Activity>) {
+    // nets whose inputs are being visited, with the next input to visit
+    let mut stack: Vec<(&'b str, usize)> = vec![(net, 0)];
+    let mut on_stack: HashSet<&'b str> = HashSet::from([net]);
+    while let Some(&(net, next)) = stack.last() {
+        let cover = match blif.drivers.get(net) {
+            Some(cell) if !activities.contains_key(net) => &blif.covers[*cell],
+            _ => {
+                activities.entry(net.to_string()).or_insert(INPUT_ACTIVITY);
+                on_stack.remove(net);
+                stack.pop();
+                continue;
+            }
+        };
+        if cover.inputs.len() <= MAX_COVER_INPUTS {
+            if let Some(input) = cover.inputs.get(next) {
+                stack.last_mut().unwrap().1 += 1;
+                if !on_stack.contains(input.as_str()) && !activities.contains_key(input) {
+                    on_stack.insert(input);
+                    stack.push((input, 0));
+                }
+                continue;
+            }
+        }
+        let activity = cover_activity(cover, activities);
+        activities.insert(net.to_string(), activity);
+        on_stack.remove(net);
+        stack.pop();
+    }
+}
+
+fn cover_activity(cover: &Cover, activities: &HashMap<String, Activity>) -> Activity {
+    let k = cover.inputs.len();
+    if k > MAX_COVER_INPUTS {
+        return INPUT_ACTIVITY;
+    }
+    // inputs closing a loop have no activity yet
+    let inputs: Vec<Activity> = cover
+        .inputs
+        .iter()
+        .map(|input| activities.get(input).copied().unwrap_or(INPUT_ACTIVITY))
+        .collect();
+    // probability of a minterm, leaving out input `skip`
+    let minterm_probability = |minterm: usize, skip: usize| -> f64 {
+        (0..k)
+            .filter(|i| *i != skip)
+            .map(|i| match minterm >> i & 1 {
+                1 => inputs[i].probability,
+                _ => 1. - inputs[i].probability,
+            })
+            .product()
+    };
+    let probability = (0..1 << k)
+        .filter(|m| cover.evaluate(*m))
+        .map(|m| minterm_probability(m, k))
+        .sum();
+    let density = (0..k)
+        .map(|i| {
+            let sensitive: f64 = (0..1_usize << k)
+                .filter(|m| m >> i & 1 == 0 && cover.evaluate(*m) != cover.evaluate(m | 1 << i))
+                .map(|m| minterm_probability(m, i))
+                .sum();
+            sensitive * inputs[i].density
+        })
+        .sum();
+
+    Activity {
+        probability,
+        density,
+    }
+}
+
+// routed wirelength of a net estimated from its box, the switched
+// capacitance being proportional to it
+fn wire_estimate(n_pin: usize, bb: &BoundBox) -> f64 {
+    let span = (bb.right - bb.left + 1) + (bb.bottom - bb.top + 1);
+    cross_count(n_pin) * span as f64
+}
+
+pub struct PowerParams {
+    pub weight: f64,
+    pub vdd: f64,
+    // clock frequency in MHz
+    pub frequency: f64,
+    // wire capacitance in fF per tile
+    pub cap_per_unit: f64,
+}
+
+impl Default for PowerParams {
+    fn default() -> Self {
+        Self {
+            weight: 1.,
+            vdd: 1.,
+            frequency: 100.,
+            cap_per_unit: 10.,
+        }
+    }
+}
+
+// dynamic interconnect power 0.5 * a * C * V^2 * f summed over all nets but
+// the global ones, which have their own network, in uW
+pub fn interconnect_power(sol: &Placement, params: &PowerParams) -> f64 {
+    let switched: f64 = sol
+        .problem
+        .nets
+        .iter()
+        .filter(|net| !net.is_global)
+        .map(|net| {
+            let mut bb = BoundBox::new();
+            for pin_id in &net.pins {
+                bb.add_coor(sol.pin2coor[*pin_id]);
+            }
+            net.activity * params.cap_per_unit * wire_estimate(net.pins.len(), &bb)
+        })
+        .sum();
+    // fF * V^2 * MHz = nW
+    0.5 * switched * params.vdd * params.vdd * params.frequency * 1e-3
+}
+
+// the power term of the annealer: weight * the activity-weighted wirelength
+// estimate of every net, kept up to date from the net boxes of the placement
+pub struct PowerCost {
+    pub weight: f64,
+    wires: Vec<f64>,
+    cost: f64,
+    nets: Vec<usize>,
+    // the wires and cost before the open move
+    journal: Journal<f64>,
+    cost_before: f64,
+}
+
+impl PowerCost {
+    pub fn new(sol: &Placement, params: &PowerParams) -> Self {
+        let wires: Vec<f64> = (0..sol.problem.nets.len())
+            .map(|n| net_wire(sol, n))
+            .collect();
+        let cost = sol
+            .problem
+            .nets
+            .iter()
+            .zip(wires.iter())
+            .map(|(net, wire)| net.activity * wire)
+            .sum();
+        Self {
+            weight: params.weight,
+            wires,
+            cost,
+            nets: Vec::new(),
+            journal: Journal::new(),
+            cost_before: 0.,
+        }
+    }
+
+    pub fn cost(&self) -> f64 {
+        self.weight * self.cost
+    }
+
+    // re-estimate the wire of the nets on `ca` and `cb` once they are
+    // swapped, returning the change in switched capacitance
+    pub fn after_swap(&mut self, sol: &Placement, ca: Coor, cb: Coor) -> f64 {
+        self.nets = sol.nets_at(&[ca, cb]);
+        let mut delta = 0.;
+        for net_id in self.nets.iter() {
+            let wire = net_wire(sol, *net_id);
+            delta += sol.problem.nets[*net_id].activity * (wire - self.wires[*net_id]);
+            self.journal.record(*net_id, &self.wires[*net_id]);
+            self.wires[*net_id] = wire;
+        }
+        self.cost += delta;
+        self.weight * delta
+    }
+
+    // open with `Placement::begin_move`; a rollback puts back the wires
+    // and the total that `after_swap` overwrote
+    pub fn begin_move(&mut self) {
+        self.journal.begin();
+        self.cost_before = self.cost;
+    }
+
+    pub fn commit(&mut self) {
+        self.journal.commit();
+    }
+
+    pub fn rollback(&mut self) {
+        self.journal.rollback(&mut self.wires);
+        self.cost = self.cost_before;
+    }
+}
+
+// global nets do not load the placed routing
+fn net_wire(sol: &Placement, net_id: usize) -> f64 {
+    let net = &sol.problem.nets[net_id];
+    if net.is_global {
+        return 0.;
+    }
+    wire_estimate(net.pins.len(), sol.bound_box(net_id))
+}
+
+#[test]
+fn should_propagate_activities_through_covers() {
+    use crate::typing::fixture::alu2;
+    let blif = alu2();
+    let activities = estimate_activities(&blif, &HashMap::new());
+    let close = |net: &str, p: f64, d: f64| {
+        let a = activities[net];
+        (a.probability - p).abs() < 1e-9 && (a.density - d).abs() < 1e-9
+    };
+    // .names pb pd pn: 11 1
+    assert!(close("pn", 0.25, 0.5));
+    // .names pb pd pm: 11 1, 00 1; every input change toggles the output
+    assert!(close("pm", 0.5, 1.));
+
+    // a fixed input activity propagates
+    let mut fixed = HashMap::new();
+    #[rustfmt::skip]
+    fixed.insert(String::from("pb"), Activity { probability: 0.5, density: 0.1 });
+    let activities = estimate_activities(&blif, &fixed);
+    assert!((activities["pm"].density - 0.6).abs() < 1e-9);
+}
+
+#[test]
+fn activities_should_survive_loops_wide_covers_and_deep_chains() {
+    let buffer = |input: &str| Cover {
+        inputs: vec![input.to_string()],
+        cubes: vec![String::from("1")],
+        on_set: true,
+    };
+    // a long buffer chain n0 -> n1 -> ..., closed into a loop by n0 = n_last
+    let n = 100_000;
+    let mut blif = BLIFInfo {
+        filename: String::from("chain"),
+        net_list: HashMap::new(),
+        drivers: HashMap::new(),
+        outputs: Vec::new(),
+        covers: Vec::new(),
+        latch_controls: Vec::new(),
+        n_pin: n + 1,
+    };
+    for i in 0..n {
+        let input = format!("n{}", (i + n - 1) % n);
+        blif.net_list.insert(format!("n{}", i), vec![i]);
+        blif.drivers.insert(format!("n{}", i), i);
+        blif.covers.push(buffer(&input));
+    }
+    // an and of 21 inputs
+    let inputs: Vec<String> = (0..21).map(|i| format!("n{}", i)).collect();
+    blif.net_list.insert(String::from("wide"), vec![n]);
+    blif.drivers.insert(String::from("wide"), n);
+    blif.covers.push(Cover {
+        inputs,
+        cubes: vec!["1".repeat(21)],
+        on_set: true,
+    });
+
+    let activities = estimate_activities(&blif, &HashMap::new());
+    assert_eq!(activities.len(), n + 1);
+    // buffers pass the activity assumed where the loop was cut
+    assert!(activities
+        .values()
+        .all(|a| a.probability == 0.5 && a.density == 0.5));
+}
+
+#[test]
+fn incremental_power_cost_should_match_rebuilt() {
+    use crate::algorithms::util::take_2;
+    use crate::typing::fixture::{alu2, make_problem};
+    use std::sync::Arc;
+    let mut problem = make_problem();
+    problem.set_activities(&estimate_activities(&alu2(), &HashMap::new()));
+    let problem = Arc::new(problem);
+    let mut sol = problem.make_placement();
+    sol.cost_force();
+
+    let params = PowerParams::default();
+    let mut power = PowerCost::new(&sol, &params);
+    for i in 0..2_000 {
+        let (ca, cb) = take_2(&problem.coors);
+        sol.begin_move();
+        power.begin_move();
+        sol.swap(ca, cb);
+        power.after_swap(&sol, ca, cb);
+        if i % 2 == 0 {
+            sol.rollback();
+            power.rollback();
+        } else {
+            sol.commit();
+            power.commit();
+        }
+    }
+    let rebuilt = PowerCost::new(&sol, &params);
+    assert!((power.cost() - rebuilt.cost()).abs() < 1e-6);
+}
@@ -0,0 +1,294 @@
+use std::collections::BinaryHeap;
+
+use super::delay_table::State;
+use crate::typing::Placement;
+
+#[derive(Clone)]
+pub struct RouterParams {
+    // tracks in every channel segment
+    pub channel_width: usize,
+    pub max_iterations: usize,
+    // present congestion factor of the first iteration and its growth
+    pub pres_fac_first: f64,
+    pub pres_fac_mult: f64,
+    pub hist_fac: f64,
+}
+
+impl RouterParams {
+    pub fn new(channel_width: usize) -> Self {
+        Self {
+            channel_width,
+            max_iterations: 50,
+            pres_fac_first: 0.5,
+            pres_fac_mult: 1.3,
+            hist_fac: 1.,
+        }
+    }
+}
+
+// the channel graph: one node per tile and one edge per channel segment
+// between two neighbouring tiles, each segment holding `channel_width` tracks.
+// horizontal segments come first, (x, y)-(x+1, y) having id x * ny + y,
+// followed by the vertical ones, (x, y)-(x, y+1) having id x * (ny-1) + y
+struct ChannelGraph {
+    nx: usize,
+    ny: usize,
+    capacity: usize,
+    occupancy: Vec<usize>,
+    history: Vec<f64>,
+}
+
+impl ChannelGraph {
+    fn new(nx: usize, ny: usize, capacity: usize) -> Self {
+        let n_edge = (nx - 1) * ny + nx * (ny - 1);
+        Self {
+            nx,
+            ny,
+            capacity,
+            occupancy: vec![0; n_edge],
+            history: vec![0.; n_edge],
+        }
+    }
+
+    // (neighbour, edge) pairs of a node
+    fn neighbours(&self, node: usize) -> impl Iterator<Item = (usize, usize)> {
+        let (nx, ny) = (self.nx, self.ny);
+        let (x, y) = (node / ny, node % ny);
+        let n_h = (nx - 1) * ny;
+        IntoIterator::into_iter([
+            (x > 0).then(|| (node - ny, (x - 1) * ny + y)),
+            (x + 1 < nx).then(|| (node + ny, x * ny + y)),
+            (y > 0).then(|| (node - 1, n_h + x * (ny - 1) + y - 1)),
+            (y + 1 < ny).then(|| (node + 1, n_h + x * (ny - 1) + y)),
+        ])
+        .flatten()
+    }
+
+    // cost of taking one more track of an edge
+    fn edge_cost(&self, edge: usize, pres_fac: f64) -> f64 {
+        let overuse = (self.occupancy[edge] + 1).saturating_sub(self.capacity);
+        (1. + self.history[edge]) * (1. + pres_fac * overuse as f64)
+    }
+
+    fn n_overused(&self) -> usize {
+        self.occupancy
+            .iter()
+            .filter(|o| **o > self.capacity)
+            .count()
+    }
+}
+
+#[allow(dead_code)]
+pub struct RoutingResult {
+    pub converged: bool,
+    pub iterations: usize,
+    // channel segments used, summed over all nets
+    pub wirelength: usize,
+    pub overused: usize,
+    pub max_occupancy: usize,
+    // overused channel segments after each iteration
+    pub overused_history: Vec<usize>,
+    // channel segments of every net
+    pub routes: Vec<Vec<usize>>,
+}
+
+impl RoutingResult {
+    pub fn print(&self) {
+        println!(
+            "routed={}   iterations={}   wirelength={}   overused={}   max_occupancy={}",
+            self.converged, self.iterations, self.wirelength, self.overused, self.max_occupancy
+        );
+        let history: Vec<String> = self
+            .overused_history
+            .iter()
+            .map(|o| o.to_string())
+            .collect();
+        println!("overused per iteration: {}", history.join(" "));
+    }
+}
+
+// PathFinder: route every net as a tree grown by shortest paths from its
+// driver, then rip up and reroute all nets while overused segments get more
+// expensive, until no segment carries more nets than it has tracks
+pub fn route(sol: &Placement, params: &RouterParams) -> RoutingResult {
+    let problem = &sol.problem;
+    let ny = problem.ny;
+    let mut graph = ChannelGraph::new(problem.nx, ny, params.channel_width);
+    let mut routes: Vec<Vec<usize>> = vec![Vec::new(); problem.nets.len()];
+    let mut overused_history = Vec::new();
+    let mut pres_fac = params.pres_fac_first;
+
+    // tiles of every net, the driver first
+    let terminals: Vec<Vec<usize>> = problem
+        .nets
+        .iter()
+        .map(|net| {
+            let mut nodes: Vec<usize> = Vec::new();
+            if net.is_global {
+                return nodes;
+            }
+            let pins = net.driver.iter().chain(net.pins.iter());
+            for (x, y) in pins.map(|p| sol.pin2coor[*p]) {
+                let node = x as usize * ny + y as usize;
+                if !nodes.contains(&node) {
+                    nodes.push(node);
+                }
+            }
+            nodes
+        })
+        .collect();
+
+    let mut converged = false;
+    for _ in 0..params.max_iterations {
+        for (net_id, nodes) in terminals.iter().enumerate() {
+            for edge in routes[net_id].iter() {
+                graph.occupancy[*edge] -= 1;
+            }
+            routes[net_id] = route_net(&graph, nodes, pres_fac);
+            for edge in routes[net_id].iter() {
+                graph.occupancy[*edge] += 1;
+            }
+        }
+
+        let overused = graph.n_overused();
+        overused_history.push(overused);
+        if overused == 0 {
+            converged = true;
+            break;
+        }
+        for (h, o) in graph.history.iter_mut().zip(graph.occupancy.iter()) {
+            if *o > params.channel_width {
+                *h += params.hist_fac * (o - params.channel_width) as f64;
+            }
+        }
+        pres_fac *= params.pres_fac_mult;
+    }
+
+    RoutingResult {
+        converged,
+        iterations: overused_history.len(),
+        wirelength: routes.iter().map(|r| r.len()).sum(),
+        overused: graph.n_overused(),
+        max_occupancy: graph.occupancy.iter().copied().max().unwrap_or(0),
+        overused_history,
+        routes,
+    }
+}
+
+// the smallest channel width that routes the placement: starting from the
+// width in `params`, double it until routing converges, then binary search
+// below it. returns the width and the routing found with it
+pub fn min_channel_width(sol: &Placement, params: &RouterParams) -> (usize, RoutingResult) {
+    let route_with = |width: usize| {
+        let mut p = params.clone();
+        p.channel_width = width;
+        route(sol, &p)
+    };
+    // lo is the widest width known to fail
+    let mut lo = 0;
+    let mut hi = params.channel_width.max(1);
+    let mut best = route_with(hi);
+    while !best.converged {
+        lo = hi;
+        hi *= 2;
+        best = route_with(hi);
+    }
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        let result = route_with(mid);
+        if result.converged {
+            hi = mid;
+            best = result;
+        } else {
+            lo = mid;
+        }
+    }
+    (hi, best)
+}
+
+// the edges of a tree connecting `nodes`, grown from the first one by
+// repeatedly adding the cheapest path from the tree to a sink not on it
+fn route_net(graph: &ChannelGraph, nodes: &[usize], pres_fac: f64) -> Vec<usize> {
+    let n_node = graph.nx * graph.ny;
+    let mut edges = Vec::new();
+    if nodes.is_empty() {
+        return edges;
+    }
+    let mut in_tree = vec![false; n_node];
+    let mut tree = vec![nodes[0]];
+    in_tree[nodes[0]] = true;
+
+    while nodes.iter().any(|n| !in_tree[*n]) {
+        let mut dist = vec![f64::INFINITY; n_node];
+        let mut prev: Vec<(usize, usize)> = vec![(0, 0); n_node];
+        let mut heap = BinaryHeap::new();
+        for node in tree.iter() {
+            dist[*node] = 0.;
+            heap.push(State(0., *node));
+        }
+        let mut sink = None;
+        while let Some(State(d, node)) = heap.pop() {
+            if d > dist[node] {
+                continue;
+            }
+            if !in_tree[node] && nodes.contains(&node) {
+                sink = Some(node);
+                break;
+            }
+            for (next, edge) in graph.neighbours(node) {
+                let cost = d + graph.edge_cost(edge, pres_fac);
+                if cost < dist[next] {
+                    dist[next] = cost;
+                    prev[next] = (node, edge);
+                    heap.push(State(cost, next));
+                }
+            }
+        }
+
+        // walk back to the tree
+        let mut node = sink.expect("unreachable sink");
+        while !in_tree[node] {
+            in_tree[node] = true;
+            tree.push(node);
+            let (from, edge) = prev[node];
+            edges.push(edge);
+            node = from;
+        }
+    }
+    edges
+}
+
+#[test]
+fn router_should_negotiate_shared_channel() {
+    use crate::typing::{Net, Problem};
+    use std::sync::Arc;
+
+    // both nets want the channel between x=1 and x=2 on the bottom row
+    #[rustfmt::skip]
+    let nets = vec![
+        Net {driver: Some(0), ..Net::new(0, "a", vec![0, 1])},
+        Net {driver: Some(2), ..Net::new(1, "b", vec![2, 3])},
+    ];
+    let problem = Arc::new(Problem::from_nets(4, 2, 4, nets));
+    #[rustfmt::skip]
+    let coor2pin = vec![
+        //   y=0      y=1
+        vec![Some(0), None], // x=0
+        vec![Some(2), None], // x=1
+        vec![Some(3), None], // x=2
+        vec![Some(1), None], // x=3
+    ];
+    let sol = Placement::from_grid(&problem, coor2pin);
+
+    let result = route(&sol, &RouterParams::new(2));
+    assert!(result.converged);
+    assert_eq!((result.iterations, result.wirelength), (1, 4));
+
+    let result = route(&sol, &RouterParams::new(1));
+    assert!(result.converged);
+    assert!(result.iterations > 1);
+    assert_eq!((result.wirelength, result.overused), (6, 0));
+
+    let (width, result) = min_channel_width(&sol, &RouterParams::new(8));
+    assert_eq!((width, result.wirelength), (1, 6));
+}
@@ -0,0 +1,440 @@
+use crate::cost::manhattan;
+use crate::typing::{Coor, Journal, PinID, Placement, Problem};
+
+pub trait DelayModel: Send + Sync {
+    fn lut_delay(&self) -> f64;
+
+    // delay of a connection from a driver at `from` to a sink at `to`
+    fn net_delay(&self, from: Coor, to: Coor) -> f64;
+}
+
+// a fixed LUT delay and a net delay linear in the manhattan distance
+pub struct LinearDelay {
+    pub lut: f64,
+    pub base: f64,
+    pub per_unit: f64,
+}
+
+impl Default for LinearDelay {
+    fn default() -> Self {
+        Self {
+            lut: 1.,
+            base: 0.2,
+            per_unit: 0.1,
+        }
+    }
+}
+
+impl DelayModel for LinearDelay {
+    fn lut_delay(&self) -> f64 {
+        self.lut
+    }
+
+    fn net_delay(&self, from: Coor, to: Coor) -> f64 {
+        self.base + self.per_unit * manhattan(from, to) as f64
+    }
+}
+
+// a driver to sink pair of a net
+pub struct Connection {
+    pub net: usize,
+    pub driver: PinID,
+    pub sink: PinID,
+}
+
+// the directed netlist over the placed cells, levelized once per problem
+pub struct TimingGraph {
+    pub connections: Vec<Connection>,
+    // cells in topological order
+    order: Vec<PinID>,
+    fanin: Vec<Vec<usize>>,
+    fanout: Vec<Vec<usize>>,
+    is_endpoint: Vec<bool>,
+}
+
+impl TimingGraph {
+    pub fn new(problem: &Problem) -> Self {
+        let n = problem.n_pin;
+        let mut connections = Vec::new();
+        let mut fanin = vec![Vec::new(); n];
+        let mut fanout = vec![Vec::new(); n];
+        let mut drives_output = vec![false; n];
+        for net in problem.nets.iter() {
+            // global nets are routed on their own network and not timed
+            let driver = match net.driver {
+                Some(driver) if !net.is_global => driver,
+                _ => continue,
+            };
+            drives_output[driver] |= net.is_output;
+            for sink in net.pins.iter().filter(|p| **p != driver) {
+                fanin[*sink].push(connections.len());
+                fanout[driver].push(connections.len());
+                connections.push(Connection {
+                    net: net.id,
+                    driver,
+                    sink: *sink,
+                });
+            }
+        }
+
+        // kahn's algorithm
+        let mut n_fanin: Vec<usize> = fanin.iter().map(|f| f.len()).collect();
+        let mut order: Vec<PinID> = (0..n).filter(|c| n_fanin[*c] == 0).collect();
+        let mut i = 0;
+        while i < order.len() {
+            for k in fanout[order[i]].iter() {
+                let sink = connections[*k].sink;
+                n_fanin[sink] -= 1;
+                if n_fanin[sink] == 0 {
+                    order.push(sink);
+                }
+            }
+            i += 1;
+        }
+        if order.len() < n {
+            panic!("combinational loop through {} cells", n - order.len());
+        }
+
+        let is_endpoint = (0..n)
+            .map(|c| drives_output[c] || fanout[c].is_empty())
+            .collect();
+        Self {
+            connections,
+            order,
+            fanin,
+            fanout,
+            is_endpoint,
+        }
+    }
+
+    // connections into and out of a cell
+    pub fn cell_connections(&self, cell: PinID) -> impl Iterator<Item = &usize> {
+        self.fanin[cell].iter().chain(self.fanout[cell].iter())
+    }
+
+    // 1 + criticality^exp of the most critical connection of every net, for
+    // weighting the wirelength of critical nets
+    pub fn net_weights(&self, report: &TimingReport, n_net: usize, crit_exp: f64) -> Vec<f64> {
+        let mut weights = vec![1.; n_net];
+        for (c, crit) in self.connections.iter().zip(report.criticalities.iter()) {
+            weights[c.net] = f64::max(weights[c.net], 1. + crit.powf(crit_exp));
+        }
+        weights
+    }
+
+    pub fn connection_delay(&self, sol: &Placement, delay: &dyn DelayModel, k: usize) -> f64 {
+        let c = &self.connections[k];
+        delay.net_delay(sol.pin2coor[c.driver], sol.pin2coor[c.sink])
+    }
+
+    pub fn analyze(&self, sol: &Placement, delay: &dyn DelayModel) -> TimingReport {
+        let n = self.order.len();
+        let lut = delay.lut_delay();
+        let delays: Vec<f64> = (0..self.connections.len())
+            .map(|k| self.connection_delay(sol, delay, k))
+            .collect();
+
+        // arrival time at the output of every cell
+        let mut arrival = vec![0.; n];
+        for c in self.order.iter() {
+            let latest = self.fanin[*c]
+                .iter()
+                .map(|k| arrival[self.connections[*k].driver] + delays[*k])
+                .fold(0., f64::max);
+            arrival[*c] = latest + lut;
+        }
+        let critical_delay = (0..n)
+            .filter(|c| self.is_endpoint[*c])
+            .map(|c| arrival[c])
+            .fold(0., f64::max);
+
+        // required time at the output of every cell
+        let mut required = vec![f64::INFINITY; n];
+        for c in self.order.iter().rev() {
+            if self.is_endpoint[*c] {
+                required[*c] = critical_delay;
+            }
+            for k in self.fanout[*c].iter() {
+                let sink = self.connections[*k].sink;
+                required[*c] = required[*c].min(required[sink] - lut - delays[*k]);
+            }
+        }
+
+        let slacks: Vec<f64> = self
+            .connections
+            .iter()
+            .zip(delays.iter())
+            .map(|(c, d)| required[c.sink] - lut - d - arrival[c.driver])
+            .collect();
+        let criticalities = slacks
+            .iter()
+            .map(|s| (1. - s / critical_delay).clamp(0., 1.))
+            .collect();
+
+        // walk back from the latest endpoint along the latest fanin
+        let mut critical_path = Vec::new();
+        let mut cell = (0..n)
+            .filter(|c| self.is_endpoint[*c])
+            .max_by(|a, b| arrival[*a].partial_cmp(&arrival[*b]).unwrap());
+        while let Some(c) = cell {
+            critical_path.push(c);
+            cell = self.fanin[c]
+                .iter()
+                .max_by(|a, b| {
+                    let ta = arrival[self.connections[**a].driver] + delays[**a];
+                    let tb = arrival[self.connections[**b].driver] + delays[**b];
+                    ta.partial_cmp(&tb).unwrap()
+                })
+                .map(|k| self.connections[*k].driver);
+        }
+        critical_path.reverse();
+
+        let endpoint_slacks = (0..n)
+            .filter(|c| self.is_endpoint[*c])
+            .map(|c| (c, required[c] - arrival[c]))
+            .collect();
+
+        TimingReport {
+            critical_delay,
+            arrival,
+            required,
+            slacks,
+            criticalities,
+            critical_path,
+            endpoint_slacks,
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub struct TimingReport {
+    pub critical_delay: f64,
+    // per cell output
+    pub arrival: Vec<f64>,
+    pub required: Vec<f64>,
+    // per connection
+    pub slacks: Vec<f64>,
+    pub criticalities: Vec<f64>,
+    // cells from a timing start point to the latest endpoint
+    pub critical_path: Vec<PinID>,
+    pub endpoint_slacks: Vec<(PinID, f64)>,
+}
+
+impl TimingReport {
+    pub fn print(&self, n_endpoint: usize) {
+        let path: Vec<String> = self
+            .critical_path
+            .iter()
+            .map(|c| format!("{}@{:.2}", c, self.arrival[*c]))
+            .collect();
+        let n_critical = self.criticalities.iter().filter(|c| **c > 0.9).count();
+        println!(
+            "critical_delay={:.2}   critical_connections={}/{}",
+            self.critical_delay,
+            n_critical,
+            self.criticalities.len()
+        );
+        println!("critical_path={}", path.join(" -> "));
+        let mut endpoints = self.endpoint_slacks.clone();
+        endpoints.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        for (cell, slack) in endpoints.iter().take(n_endpoint) {
+            println!("endpoint={:5}   slack={:.2}", cell, slack);
+        }
+    }
+}
+
+pub struct TimingParams {
+    // weight of the timing cost against the wirelength cost
+    pub lambda: f64,
+    pub crit_exp: f64,
+    // temperatures between two timing analyses
+    pub refresh_every: usize,
+}
+
+impl TimingParams {
+    pub fn new(lambda: f64, crit_exp: f64, refresh_every: usize) -> Self {
+        if refresh_every == 0 {
+            panic!("timing analyses have to be at least 1 temperature apart");
+        }
+        Self {
+            lambda,
+            crit_exp,
+            refresh_every,
+        }
+    }
+}
+
+// the timing term of the annealer: the sum over connections of their delay
+// weighted by criticality^crit_exp, with criticalities from the last analysis
+pub struct TimingCost<'a> {
+    pub graph: TimingGraph,
+    params: &'a TimingParams,
+    weights: Vec<f64>,
+    delays: Vec<f64>,
+    cost: f64,
+    affected: Vec<usize>,
+    // the delays and cost before the open move
+    journal: Journal<f64>,
+    cost_before: f64,
+}
+
+impl<'a> TimingCost<'a> {
+    pub fn new(sol: &Placement, params: &'a TimingParams) -> Self {
+        let mut timing = Self {
+            graph: TimingGraph::new(&sol.problem),
+            params,
+            weights: Vec::new(),
+            delays: Vec::new(),
+            cost: 0.,
+            affected: Vec::new(),
+            journal: Journal::new(),
+            cost_before: 0.,
+        };
+        timing.refresh(sol);
+        timing
+    }
+
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+
+    // run a timing analysis and reweight the connections by their criticality
+    pub fn refresh(&mut self, sol: &Placement) -> TimingReport {
+        let delay = sol.problem.delay_model.as_ref();
+        let report = self.graph.analyze(sol, delay);
+        let exp = self.params.crit_exp;
+        self.weights = report.criticalities.iter().map(|c| c.powf(exp)).collect();
+        self.delays = (0..self.graph.connections.len())
+            .map(|k| self.graph.connection_delay(sol, delay, k))
+            .collect();
+        self.cost = self
+            .weights
+            .iter()
+            .zip(self.delays.iter())
+            .map(|(w, d)| w * d)
+            .sum();
+        report
+    }
+
+    // recompute the delay of every connection into or out of the cells on
+    // `ca` and `cb`, returning the change in criticality-weighted delay
+    pub fn after_swap(&mut self, sol: &Placement, ca: Coor, cb: Coor) -> f64 {
+        self.affected.clear();
+        for coor in [ca, cb] {
+            if let Some(cell) = sol.coor2pin(coor) {
+                for k in self.graph.cell_connections(cell) {
+                    if !self.affected.contains(k) {
+                        self.affected.push(*k);
+                    }
+                }
+            }
+        }
+        let mut delta = 0.;
+        for k in self.affected.iter() {
+            let d = self
+                .graph
+                .connection_delay(sol, sol.problem.delay_model.as_ref(), *k);
+            delta += self.weights[*k] * (d - self.delays[*k]);
+            self.journal.record(*k, &self.delays[*k]);
+            self.delays[*k] = d;
+        }
+        self.cost += delta;
+        delta
+    }
+
+    // the weights stay fixed between refreshes, so a rollback only has to
+    // put back the connection delays and the total
+    pub fn begin_move(&mut self) {
+        self.journal.begin();
+        self.cost_before = self.cost;
+    }
+
+    pub fn commit(&mut self) {
+        self.journal.commit();
+    }
+
+    pub fn rollback(&mut self) {
+        self.journal.rollback(&mut self.delays);
+        self.cost = self.cost_before;
+    }
+}
+
+#[test]
+fn should_compute_slack_and_criticality() {
+    use crate::typing::Net;
+    use std::sync::Arc;
+
+    // cell 0 drives 1 and 2, cell 1 drives 2, cell 2 is a primary output
+    #[rustfmt::skip]
+    let nets = vec![
+        Net::new(0, "in", vec![0]),
+        Net {driver: Some(0), ..Net::new(1, "a", vec![0, 1, 2])},
+        Net {driver: Some(1), ..Net::new(2, "b", vec![1, 2])},
+        Net {driver: Some(2), is_output: true, ..Net::new(3, "c", vec![2])},
+    ];
+    let problem = Arc::new(Problem::from_nets(4, 5, 3, nets));
+    #[rustfmt::skip]
+    let coor2pin = vec![
+        //   y=0      y=1   y=2   y=3   y=4
+        vec![Some(0), None, None, None, None   ], // x=0
+        vec![None,    None, None, None, None   ], // x=1
+        vec![None,    None, None, None, None   ], // x=2
+        vec![Some(1), None, None, None, Some(2)], // x=3
+    ];
+    let sol = Placement::from_grid(&problem, coor2pin);
+
+    #[rustfmt::skip]
+    let delay = LinearDelay { lut: 1., base: 0., per_unit: 1. };
+    let graph = TimingGraph::new(&problem);
+    let report = graph.analyze(&sol, &delay);
+
+    assert_eq!(report.critical_delay, 10.);
+    assert_eq!(report.critical_path, vec![0, 1, 2]);
+    assert_eq!(report.endpoint_slacks, vec![(2, 0.)]);
+    for (c, slack) in graph.connections.iter().zip(report.slacks.iter()) {
+        let expected = if (c.driver, c.sink) == (0, 2) { 1. } else { 0. };
+        assert_eq!(*slack, expected);
+    }
+    assert!(report.criticalities.contains(&0.9));
+    // net a holds a fully critical connection, net c none
+    let weights = graph.net_weights(&report, 4, 1.);
+    assert_eq!(weights, vec![1., 2., 2., 1.]);
+}
+
+#[test]
+fn incremental_timing_cost_should_match_refresh() {
+    use crate::algorithms::util::take_2;
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    let mut sol = problem.make_placement();
+    sol.cost_force();
+
+    let params = TimingParams::new(0.5, 2., 1);
+    let mut timing = TimingCost::new(&sol, &params);
+    let weights = timing.weights.clone();
+    for i in 0..2_000 {
+        let (ca, cb) = take_2(&problem.coors);
+        sol.begin_move();
+        timing.begin_move();
+        sol.swap(ca, cb);
+        timing.after_swap(&sol, ca, cb);
+        if i % 2 == 0 {
+            sol.rollback();
+            timing.rollback();
+        } else {
+            sol.commit();
+            timing.commit();
+        }
+    }
+    let incremental = timing.cost();
+    // recompute the delays under the same weights
+    timing.refresh(&sol);
+    timing.weights = weights;
+    let recomputed: f64 = timing
+        .weights
+        .iter()
+        .zip(timing.delays.iter())
+        .map(|(w, d)| w * d)
+        .sum();
+    assert!((incremental - recomputed).abs() < 1e-6);
+}
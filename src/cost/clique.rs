@@ -0,0 +1,68 @@
+use super::{manhattan, CostModel};
+use crate::typing::{Coor, Net};
+
+// every pair of pins is connected; edges are weighted by 1/(n-1) so that a
+// 2-pin net costs its manhattan length
+pub struct Clique;
+
+impl CostModel for Clique {
+    fn name(&self) -> &'static str {
+        "clique"
+    }
+
+    fn net_cost(&self, net: &Net, pin2coor: &[Coor]) -> f64 {
+        let n = net.pins.len();
+        if n < 2 {
+            return 0.;
+        }
+        let mut length = 0;
+        for (i, pa) in net.pins.iter().enumerate() {
+            for pb in &net.pins[i + 1..] {
+                length += manhattan(pin2coor[*pa], pin2coor[*pb]);
+            }
+        }
+        length as f64 / (n - 1) as f64
+    }
+}
+
+// every pin is connected to a star node at the centroid of the net
+pub struct Star;
+
+impl CostModel for Star {
+    fn name(&self) -> &'static str {
+        "star"
+    }
+
+    fn net_cost(&self, net: &Net, pin2coor: &[Coor]) -> f64 {
+        let n = net.pins.len();
+        if n < 2 {
+            return 0.;
+        }
+        let (mut cx, mut cy) = (0., 0.);
+        for pin_id in &net.pins {
+            let (x, y) = pin2coor[*pin_id];
+            cx += x as f64;
+            cy += y as f64;
+        }
+        cx /= n as f64;
+        cy /= n as f64;
+        net.pins
+            .iter()
+            .map(|pin_id| {
+                let (x, y) = pin2coor[*pin_id];
+                (x as f64 - cx).abs() + (y as f64 - cy).abs()
+            })
+            .sum()
+    }
+}
+
+#[test]
+fn clique_and_star_should_match_length_on_2_pin_net() {
+    let pin2coor = vec![(0, 0), (2, 1), (3, 2)];
+    let net = Net::new(0, "0", vec![0, 1]);
+    assert_eq!(Clique.net_cost(&net, &pin2coor), 3.);
+    assert_eq!(Star.net_cost(&net, &pin2coor), 3.);
+
+    let net = Net::new(0, "0", vec![0, 1, 2]);
+    assert_eq!(Clique.net_cost(&net, &pin2coor), (3. + 5. + 2.) / 2.);
+}
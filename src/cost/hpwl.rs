@@ -0,0 +1,30 @@
+use super::CostModel;
+use crate::typing::{BoundBox, Coor, Net};
+
+// plain half-perimeter wirelength
+pub struct Hpwl;
+
+impl CostModel for Hpwl {
+    fn name(&self) -> &'static str {
+        "hpwl"
+    }
+
+    fn net_cost(&self, net: &Net, pin2coor: &[Coor]) -> f64 {
+        let mut bb = BoundBox::new();
+        for pin_id in &net.pins {
+            bb.add_coor(pin2coor[*pin_id]);
+        }
+        bb.half_perimeter() as f64
+    }
+
+    fn bound_box_cost(&self, _net: &Net, bb: &BoundBox) -> Option<f64> {
+        Some(bb.half_perimeter() as f64)
+    }
+}
+
+#[test]
+fn hpwl_should_be_half_perimeter() {
+    let net = Net::new(0, "0", vec![0, 1, 2]);
+    let pin2coor = vec![(0, 0), (2, 1), (3, 2)];
+    assert_eq!(Hpwl.net_cost(&net, &pin2coor), 5.);
+}
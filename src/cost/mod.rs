@@ -0,0 +1,56 @@
+mod clique;
+mod hpwl;
+mod steiner;
+mod vpr;
+
+pub use clique::{Clique, Star};
+pub use hpwl::Hpwl;
+pub use steiner::Steiner;
+pub use vpr::{cross_count, VprBoundBox};
+
+use crate::typing::{BoundBox, Coor, Net};
+
+// a net-level wirelength model. the placement cost is the sum of the cost of
+// every net, so a model only needs to know how to evaluate a single net
+pub trait CostModel: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn net_cost(&self, net: &Net, pin2coor: &[Coor]) -> f64;
+
+    // models that depend only on the bounding box of a net return Some here,
+    // which lets `Placement` evaluate them from its incremental net boxes
+    fn bound_box_cost(&self, _net: &Net, _bb: &BoundBox) -> Option<f64> {
+        None
+    }
+}
+
+pub fn manhattan(a: Coor, b: Coor) -> usize {
+    a.0.abs_diff(b.0) as usize + a.1.abs_diff(b.1) as usize
+}
+
+// `vpr_bb:<W>` divides by a channel width of W, plain `vpr_bb` by 1
+pub fn from_name(name: &str) -> Option<Box<dyn CostModel>> {
+    if let Some(width) = name.strip_prefix("vpr_bb:") {
+        let width: f64 = width.parse().ok().filter(|w| *w > 0.)?;
+        return Some(Box::new(VprBoundBox::new(width)));
+    }
+    match name {
+        "hpwl" => Some(Box::new(Hpwl)),
+        "vpr_bb" => Some(Box::new(VprBoundBox::new(1.))),
+        "clique" => Some(Box::new(Clique)),
+        "star" => Some(Box::new(Star)),
+        "steiner" => Some(Box::new(Steiner)),
+        _ => None,
+    }
+}
+
+#[test]
+fn vpr_bb_should_be_normalized_by_the_given_channel_width() {
+    let net = Net::new(0, "0", vec![0, 1]);
+    let pin2coor = vec![(0, 0), (2, 1)];
+    let cost = |name: &str| from_name(name).unwrap().net_cost(&net, &pin2coor);
+    assert_eq!(cost("vpr_bb"), 3. + 2.);
+    assert_eq!(cost("vpr_bb:4"), (3. + 2.) / 4.);
+    assert!(from_name("vpr_bb:0").is_none());
+    assert!(from_name("vpr_bb:wide").is_none());
+}
@@ -0,0 +1,209 @@
+use super::{manhattan, CostModel};
+use crate::typing::{BoundBox, Coor, Net};
+
+// rectilinear steiner minimal tree length. nets with up to 3 pins are
+// solved by their half-perimeter and 4-pin nets by trying every pair of
+// hanan grid points as steiner points, which stands in for FLUTE's lookup
+// tables. nets up to MAX_STEINER_PINS pins use the batched iterated
+// 1-steiner heuristic for at most MAX_ROUNDS rounds, larger ones their
+// minimum spanning tree, which is at most 1.5 times as long
+pub struct Steiner;
+
+const MAX_STEINER_PINS: usize = 16;
+const MAX_ROUNDS: usize = 4;
+
+impl CostModel for Steiner {
+    fn name(&self) -> &'static str {
+        "steiner"
+    }
+
+    fn net_cost(&self, net: &Net, pin2coor: &[Coor]) -> f64 {
+        let coors: Vec<Coor> = net.pins.iter().map(|pin_id| pin2coor[*pin_id]).collect();
+        rsmt_length(&coors) as f64
+    }
+}
+
+pub fn rsmt_length(coors: &[Coor]) -> usize {
+    let mut points = coors.to_vec();
+    points.sort_unstable();
+    points.dedup();
+
+    match points.len() {
+        0 | 1 => 0,
+        2 | 3 => {
+            let mut bb = BoundBox::new();
+            points.iter().for_each(|c| bb.add_coor(*c));
+            bb.half_perimeter()
+        }
+        4 => exact_rsmt_length(&points),
+        n if n <= MAX_STEINER_PINS => iterated_1_steiner_length(points),
+        _ => mst_length(&points),
+    }
+}
+
+fn hanan_grid(points: &[Coor]) -> Vec<Coor> {
+    let mut xs: Vec<u16> = points.iter().map(|c| c.0).collect();
+    let mut ys: Vec<u16> = points.iter().map(|c| c.1).collect();
+    xs.sort_unstable();
+    xs.dedup();
+    ys.sort_unstable();
+    ys.dedup();
+
+    let mut grid = Vec::new();
+    for x in xs.iter() {
+        for y in ys.iter() {
+            if !points.contains(&(*x, *y)) {
+                grid.push((*x, *y));
+            }
+        }
+    }
+    grid
+}
+
+// a tree over n pins needs at most n - 2 steiner points, all on the hanan grid
+fn exact_rsmt_length(points: &[Coor]) -> usize {
+    let candidates = hanan_grid(points);
+    let mut best = mst_length(points);
+    let mut with_steiner = points.to_vec();
+    for (i, a) in candidates.iter().enumerate() {
+        with_steiner.push(*a);
+        best = best.min(mst_length(&with_steiner));
+        for b in &candidates[i + 1..] {
+            with_steiner.push(*b);
+            best = best.min(mst_length(&with_steiner));
+            with_steiner.pop();
+        }
+        with_steiner.pop();
+    }
+    best
+}
+
+fn iterated_1_steiner_length(mut points: Vec<Coor>) -> usize {
+    let n_pin = points.len();
+    let mut length = mst_length(&points);
+    for _ in 0..MAX_ROUNDS {
+        // rank every hanan point by how much it shortens the current tree
+        let mut gains: Vec<(usize, Coor)> = hanan_grid(&points)
+            .into_iter()
+            .filter_map(|c| {
+                points.push(c);
+                let l = mst_length(&points);
+                points.pop();
+                if l < length {
+                    Some((length - l, c))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if gains.is_empty() {
+            return length;
+        }
+        gains.sort_unstable_by_key(|g| std::cmp::Reverse(g.0));
+
+        // add the candidates in a batch, skipping those whose gain was used
+        // up by the points added before them
+        for (_, c) in gains {
+            points.push(c);
+            let l = mst_length(&points);
+            if l < length {
+                length = l;
+            } else {
+                points.pop();
+            }
+        }
+
+        // a steiner point left with at most two tree edges shortens nothing:
+        // the edge joining its neighbours is no longer than the two it had
+        let degrees = mst_degrees(&points);
+        let mut i = points.len();
+        while i > n_pin {
+            i -= 1;
+            if degrees[i] <= 2 {
+                points.swap_remove(i);
+            }
+        }
+        length = mst_length(&points);
+    }
+    length
+}
+
+pub fn mst_length(points: &[Coor]) -> usize {
+    mst(points).0
+}
+
+// the number of tree edges at every point
+fn mst_degrees(points: &[Coor]) -> Vec<usize> {
+    let mut degrees = vec![0; points.len()];
+    for (i, parent) in mst(points).1.iter().enumerate() {
+        if let Some(parent) = parent {
+            degrees[i] += 1;
+            degrees[*parent] += 1;
+        }
+    }
+    degrees
+}
+
+// prim's algorithm on the complete graph with manhattan edge lengths,
+// returning the tree length and the parent of every point
+fn mst(points: &[Coor]) -> (usize, Vec<Option<usize>>) {
+    let n = points.len();
+    let mut parent = vec![None; n];
+    if n < 2 {
+        return (0, parent);
+    }
+    let mut in_tree = vec![false; n];
+    let mut dist = vec![usize::MAX; n];
+    dist[0] = 0;
+    let mut length = 0;
+    for _ in 0..n {
+        let mut next = n;
+        for i in 0..n {
+            if !in_tree[i] && (next == n || dist[i] < dist[next]) {
+                next = i;
+            }
+        }
+        in_tree[next] = true;
+        length += dist[next];
+        for i in 0..n {
+            if !in_tree[i] {
+                let d = manhattan(points[next], points[i]);
+                if d < dist[i] {
+                    dist[i] = d;
+                    parent[i] = Some(next);
+                }
+            }
+        }
+    }
+    (length, parent)
+}
+
+#[test]
+fn rsmt_should_find_steiner_points() {
+    // a cross needs its center as steiner point
+    let cross = [(0, 1), (1, 0), (2, 1), (1, 2)];
+    assert_eq!(mst_length(&cross), 6);
+    assert_eq!(rsmt_length(&cross), 4);
+
+    // the corners and center of a square form an H
+    let square = [(0, 0), (2, 0), (0, 2), (2, 2), (1, 1)];
+    assert_eq!(mst_length(&square), 8);
+    assert_eq!(rsmt_length(&square), 6);
+
+    assert_eq!(rsmt_length(&[(0, 0), (3, 1), (1, 2)]), 5);
+
+    // a heuristic tree lies between the half-perimeter and the spanning
+    // tree, and nets past MAX_STEINER_PINS get their spanning tree
+    for n in [10, 40] {
+        let points: Vec<Coor> = (0..n as u16)
+            .map(|i| ((i * 7) % 13, (i * 11) % 17))
+            .collect();
+        let mut bb = BoundBox::new();
+        points.iter().for_each(|c| bb.add_coor(*c));
+        let length = rsmt_length(&points);
+        assert!(bb.half_perimeter() <= length && length <= mst_length(&points));
+        if n > MAX_STEINER_PINS {
+            assert_eq!(length, mst_length(&points));
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use super::CostModel;
+use crate::typing::{BoundBox, Coor, Net};
+
+// VPR's bounding box cost: the half-perimeter (counted in channel segments) is
+// scaled by q(n) to compensate for the underestimation on nets with many
+// pins, and divided by the channel width in each direction
+pub struct VprBoundBox {
+    pub chan_width_x: f64,
+    pub chan_width_y: f64,
+}
+
+impl VprBoundBox {
+    pub fn new(chan_width: f64) -> Self {
+        Self {
+            chan_width_x: chan_width,
+            chan_width_y: chan_width,
+        }
+    }
+}
+
+// q(n) for n = 1..=50 pins, from Cheng's "RISA: accurate and efficient
+// placement routability modeling" as tabulated in VPR
+#[rustfmt::skip]
+const CROSS_COUNT: [f64; 50] = [
+    1.0,    1.0,    1.0,    1.0828, 1.1536, 1.2206, 1.2823, 1.3385, 1.3991, 1.4493,
+    1.4974, 1.5455, 1.5937, 1.6418, 1.6899, 1.7304, 1.7709, 1.8114, 1.8519, 1.8924,
+    1.9288, 1.9652, 2.0015, 2.0379, 2.0743, 2.1061, 2.1379, 2.1698, 2.2016, 2.2334,
+    2.2646, 2.2958, 2.3271, 2.3583, 2.3895, 2.4187, 2.4479, 2.4772, 2.5064, 2.5356,
+    2.5610, 2.5864, 2.6117, 2.6371, 2.6625, 2.6887, 2.7148, 2.7410, 2.7671, 2.7933,
+];
+
+pub fn cross_count(n_pin: usize) -> f64 {
+    if n_pin == 0 {
+        0.
+    } else if n_pin <= 50 {
+        CROSS_COUNT[n_pin - 1]
+    } else {
+        2.7933 + 0.02616 * (n_pin - 50) as f64
+    }
+}
+
+impl CostModel for VprBoundBox {
+    fn name(&self) -> &'static str {
+        "vpr_bb"
+    }
+
+    fn net_cost(&self, net: &Net, pin2coor: &[Coor]) -> f64 {
+        let mut bb = BoundBox::new();
+        for pin_id in &net.pins {
+            bb.add_coor(pin2coor[*pin_id]);
+        }
+        self.bound_box_cost(net, &bb).unwrap()
+    }
+
+    fn bound_box_cost(&self, net: &Net, bb: &BoundBox) -> Option<f64> {
+        let q = cross_count(net.pins.len());
+        let span_x = (bb.right - bb.left + 1) as f64;
+        let span_y = (bb.bottom - bb.top + 1) as f64;
+        Some(q * (span_x / self.chan_width_x + span_y / self.chan_width_y))
+    }
+}
+
+#[test]
+fn vpr_bb_should_apply_fanout_correction() {
+    let model = VprBoundBox::new(2.);
+    let pin2coor = vec![(0, 0), (2, 1), (3, 2), (1, 1)];
+
+    let net = Net::new(0, "0", vec![0, 1]);
+    assert_eq!(model.net_cost(&net, &pin2coor), (3. + 2.) / 2.);
+
+    let net = Net::new(0, "0", vec![0, 1, 2, 3]);
+    assert_eq!(model.net_cost(&net, &pin2coor), 1.0828 * (4. + 3.) / 2.);
+}
@@ -1,57 +1,127 @@
 mod algorithms;
+mod analysis;
+mod cost;
 mod typing;
 
-use crate::algorithms::annealing_placement;
-use crate::algorithms::genetic_placement;
-use typing::{BLIFInfo, Problem};
+use crate::algorithms::nsga2_placement;
+use crate::algorithms::{annealing_placement, annealing_placement_with};
+use crate::algorithms::{genetic_placement, genetic_placement_with};
+use std::sync::Arc;
+use std::time::Duration;
+use typing::{BLIFInfo, Placement, Problem};
+
+// the wirelength and timing of a placer's best placement, and its congestion
+// and power when they were optimized
+fn report(
+    sol: &Placement,
+    congestion: Option<&analysis::CongestionParams>,
+    power: Option<&analysis::PowerParams>,
+) {
+    algorithms::util::print_report(sol, sol.problem.delay_model.as_ref());
+    if let Some(c_params) = congestion {
+        let map = analysis::CongestionMap::from_placement(sol, c_params.capacity);
+        println!(
+            "overflow={:.1}   max_demand={:.2}",
+            map.overflow(),
+            map.max_demand()
+        );
+        if let Some(path) = &c_params.export_path {
+            map.write_csv(path).expect("cannot write congestion map");
+        }
+    }
+    if let Some(p) = power {
+        println!(
+            "interconnect_power={:.1}uW",
+            analysis::interconnect_power(sol, p)
+        );
+    }
+}
 
 #[test]
 fn pair_sa() {
     let filename = "benchmarks/pair.blif";
     let info = BLIFInfo::from_file(filename);
-    let problem = Problem::new(&info, 50, 40);
+    let problem = Arc::new(Problem::new(&info, 50, 40));
     let params = algorithms::AnnealingParams {
-        t_init: 5.0,
-        t_decrease_factor: 0.9,
-        t_terminate: 0.1,
+        schedule: algorithms::Schedule::Fixed {
+            t_init: 5.0,
+            t_decrease_factor: 0.9,
+            t_terminate: 0.1,
+        },
+        inner_num: 100.,
+        check_every: None,
+        moves: algorithms::MoveParams::default(),
+        range_limit: None,
+        congestion: None,
+        timing: None,
+        power: None,
+        pareto: None,
+        route: None,
+        budget: algorithms::Budget::default(),
     };
     // cost = 4507; time = 70.31s
-    annealing_placement(&problem, &params);
+    let result = annealing_placement_with(&problem, &params, &mut algorithms::ConsoleObserver);
+    result.print();
+    report(&result.best, None, None);
 }
 
 #[test]
 fn apex1_sa() {
     let filename = "benchmarks/apex1.blif";
     let info = BLIFInfo::from_file(filename);
-    let problem = Problem::new(&info, 50, 40);
+    let problem = Arc::new(Problem::new(&info, 50, 40));
     let params = algorithms::AnnealingParams {
-        t_init: 5.0,
-        t_decrease_factor: 0.9,
-        t_terminate: 0.1,
+        schedule: algorithms::Schedule::Fixed {
+            t_init: 5.0,
+            t_decrease_factor: 0.9,
+            t_terminate: 0.1,
+        },
+        inner_num: 100.,
+        check_every: None,
+        moves: algorithms::MoveParams::default(),
+        range_limit: None,
+        congestion: None,
+        timing: None,
+        power: None,
+        pareto: None,
+        route: None,
+        budget: algorithms::Budget::default(),
     };
     // cost = 7325 ; time = 59.83s
-    annealing_placement(&problem, &params);
+    let result = annealing_placement_with(&problem, &params, &mut algorithms::ConsoleObserver);
+    result.print();
+    report(&result.best, None, None);
 }
 
 #[test]
 fn alu2_sa() {
-    let filename = "benchmarks/alu2.blif";
-    let info = BLIFInfo::from_file(filename);
-    let problem = Problem::new(&info, 50, 40);
+    let problem = typing::fixture::make_fixture();
     let params = algorithms::AnnealingParams {
-        t_init: 5.0,
-        t_decrease_factor: 0.9,
-        t_terminate: 0.1,
+        schedule: algorithms::Schedule::Fixed {
+            t_init: 5.0,
+            t_decrease_factor: 0.9,
+            t_terminate: 0.1,
+        },
+        inner_num: 100.,
+        check_every: Some(100_000),
+        moves: algorithms::MoveParams::default(),
+        range_limit: None,
+        congestion: None,
+        timing: None,
+        power: None,
+        pareto: None,
+        route: None,
+        budget: algorithms::Budget::default(),
     };
     // cost = 1394 ; time = 9.57s
-    annealing_placement(&problem, &params);
+    let result = annealing_placement_with(&problem, &params, &mut algorithms::ConsoleObserver);
+    result.print();
+    report(&result.best, None, None);
 }
 
 #[test]
 fn genetic() {
-    let filename = "benchmarks/alu2.blif";
-    let info = BLIFInfo::from_file(filename);
-    let problem = Problem::new(&info, 50, 40);
+    let problem = typing::fixture::make_fixture();
     let params = algorithms::GeneticParams {
         n_generation: 26_000,
         n_population: 200,
@@ -59,38 +129,404 @@ fn genetic() {
         n_select: 80,
         n_crossover: 60,
         p_mutation: 1.0,
+        moves: algorithms::MoveParams::default(),
+        range_limit: None,
+        pareto: None,
+        route: None,
+        budget: algorithms::Budget::default(),
     };
     println!(
         "\"cross={:.2}, mut={:.2}\"",
         params.n_crossover, params.p_mutation
     );
     // cost =  ; time =
-    genetic_placement(&problem, &params);
+    let mut observer =
+        algorithms::Throttled::new(algorithms::ConsoleObserver, Duration::from_secs(1));
+    let result = genetic_placement_with(&problem, &params, &mut observer);
+    result.print();
+    report(&result.best, None, None);
 }
 
 fn main() {
-    let filename = "benchmarks/alu2.blif";
-    let info = BLIFInfo::from_file(filename);
-    let problem = Problem::new(&info, 50, 40);
-    let params = algorithms::AnnealingParams {
-        t_init: 5.0,
-        t_decrease_factor: 0.9,
-        t_terminate: 0.1,
-    };
-    // cost = 1394 ; time = 9.57s
-    annealing_placement(&problem, &params);
+    let mut cost_model: Option<String> = None;
+    let mut arch: Option<typing::Architecture> = None;
+    let mut congestion: Option<analysis::CongestionParams> = None;
+    let mut timing: Option<analysis::TimingParams> = None;
+    let mut route: Option<analysis::RouterParams> = None;
+    let mut power: Option<analysis::PowerParams> = None;
+    let mut activity_file: Option<String> = None;
+    let mut pareto_dir: Option<String> = None;
+    let mut weight_file: Option<String> = None;
+    let mut global_nets = typing::GlobalNetParams::default();
+    let mut criticality_weights: Option<f64> = None;
+    let mut nsga2: Option<algorithms::SecondObjective> = None;
+    let mut moves = algorithms::MoveParams::default();
+    let mut range_limit = None;
+    let mut adaptive_schedule = true;
+    let mut inner_num = 100.;
+    let mut budget = algorithms::Budget::default();
+    let mut min_width = false;
+    let mut all_benchmarks = false;
+    let mut history_dir: Option<String> = None;
+    let mut quiet = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect("missing value for option");
+        match arg.as_str() {
+            "--cost" => cost_model = Some(value()),
+            "--congestion-weight" | "--congestion-capacity" | "--congestion-map" => {
+                let c = congestion.get_or_insert(analysis::CongestionParams {
+                    weight: 1.,
+                    capacity: 2.,
+                    export_path: None,
+                });
+                match arg.as_str() {
+                    "--congestion-weight" => c.weight = value().parse().unwrap(),
+                    "--congestion-capacity" => c.capacity = value().parse().unwrap(),
+                    _ => c.export_path = Some(value()),
+                }
+            }
+            "--power-weight" => {
+                let p = power.get_or_insert_with(analysis::PowerParams::default);
+                p.weight = value().parse().unwrap();
+            }
+            // net activities overriding the propagated ones
+            "--activity" => activity_file = Some(value()),
+            // archive non-dominated placements and export them to a directory
+            "--pareto" => pareto_dir = Some(value()),
+            // also run the multi-objective GA against `span` or `delay`
+            "--nsga2" => {
+                nsga2 = Some(match value().as_str() {
+                    "span" => algorithms::SecondObjective::MaxNetSpan,
+                    "delay" => algorithms::SecondObjective::CriticalDelay,
+                    other => panic!("unknown objective {}", other),
+                })
+            }
+            // the mix of moves both placers draw from, e.g. swap=1,shift=0.2
+            "--moves" => moves = algorithms::MoveParams::parse(&value()),
+            // keep moves within a window that holds the acceptance rate at
+            // this value, e.g. 0.44; moves span the whole chip otherwise
+            "--range-limit" => {
+                range_limit = Some(algorithms::RangeParams {
+                    target_acceptance: value().parse().unwrap(),
+                })
+            }
+            // `fixed` cools from 5 to 0.1 by 0.9, `adaptive` follows VPR
+            "--schedule" => {
+                adaptive_schedule = match value().as_str() {
+                    "fixed" => false,
+                    "adaptive" => true,
+                    other => panic!("unknown schedule {}", other),
+                }
+            }
+            // moves per temperature, in units of n_pin^(4/3)
+            "--inner-num" => inner_num = value().parse().unwrap(),
+            // give every placer this many seconds, or moves or generations
+            "--time-budget" => {
+                budget.time = Some(Duration::from_secs_f64(value().parse().unwrap()))
+            }
+            "--max-steps" => budget.max_steps = Some(value().parse().unwrap()),
+            "--global-pattern" => global_nets.patterns.push(value()),
+            "--global-fanout" => global_nets.fanout_threshold = Some(value().parse().unwrap()),
+            "--net-weights" => weight_file = Some(value()),
+            // weigh nets by the criticality found after a quick first placement
+            "--criticality-weights" => criticality_weights = Some(value().parse().unwrap()),
+            "--arch" => arch = Some(typing::Architecture::from_file(&value())),
+            "--channel-width" => {
+                route = Some(analysis::RouterParams::new(value().parse().unwrap()));
+            }
+            // binary search the smallest routable channel width of each result
+            "--min-width" => min_width = true,
+            // place every benchmark instead of alu2 only
+            "--all-benchmarks" => all_benchmarks = true,
+            // write every temperature and generation to <dir>/<benchmark>_<placer>.csv
+            "--history" => history_dir = Some(value()),
+            // no line per temperature or generation
+            "--quiet" => quiet = true,
+            "--timing-lambda" | "--crit-exp" => {
+                let t = timing.get_or_insert(analysis::TimingParams::new(0.5, 1., 1));
+                match arg.as_str() {
+                    "--timing-lambda" => t.lambda = value().parse().unwrap(),
+                    _ => t.crit_exp = value().parse().unwrap(),
+                }
+            }
+            _ => panic!("unknown option {}", arg),
+        }
+    }
 
-    let params = algorithms::GeneticParams {
+    // the first ctrl-c stops the placers, which still report their best
+    // placement so far, the second one ends the program
+    let cancel = algorithms::CancelToken::new();
+    cancel_on_sigint(&cancel);
+    budget.cancel = Some(cancel.clone());
+
+    let mut filenames = vec![String::from("benchmarks/alu2.blif")];
+    if all_benchmarks {
+        filenames = std::fs::read_dir("benchmarks")
+            .expect("cannot read benchmarks")
+            .map(|entry| entry.unwrap().path().to_string_lossy().into_owned())
+            .filter(|path| path.ends_with(".blif"))
+            .collect();
+        filenames.sort();
+    }
+
+    // the width of a previous routing is a good first guess for the search
+    let search_from = analysis::RouterParams::new(route.as_ref().map_or(8, |r| r.channel_width));
+    // vpr_bb is normalized by the channel width the results are routed at
+    let cost_model = match (cost_model, &route) {
+        (Some(name), Some(r)) if name == "vpr_bb" => Some(format!("vpr_bb:{}", r.channel_width)),
+        (cost_model, _) => cost_model,
+    };
+    let mut sa_params = algorithms::AnnealingParams {
+        schedule: if adaptive_schedule {
+            algorithms::Schedule::Adaptive
+        } else {
+            algorithms::Schedule::Fixed {
+                t_init: 5.0,
+                t_decrease_factor: 0.9,
+                t_terminate: 0.1,
+            }
+        },
+        inner_num,
+        check_every: None,
+        moves: moves.clone(),
+        range_limit: range_limit.clone(),
+        congestion,
+        timing,
+        power,
+        pareto: pareto_dir
+            .as_ref()
+            .map(|_| algorithms::ParetoParams::default()),
+        route: route.clone(),
+        budget: budget.clone(),
+    };
+    let mut ga_params = algorithms::GeneticParams {
         n_generation: 26_000,
         n_population: 100,
         n_elite: 30,
         n_select: 40,
         n_crossover: 30,
         p_mutation: 1.0,
+        moves: moves.clone(),
+        range_limit: range_limit.clone(),
+        // evaluating the objectives of a whole population is slow
+        pareto: pareto_dir.as_ref().map(|_| algorithms::ParetoParams {
+            every: 500,
+            ..algorithms::ParetoParams::default()
+        }),
+        route,
+        budget: budget.clone(),
     };
-    // cost =  ; time =
-    genetic_placement(&problem, &params);
+
+    let nsga2_params = nsga2.map(|second| algorithms::Nsga2Params {
+        n_generation: 5_000,
+        n_population: 100,
+        p_mutation: 1.0,
+        moves: moves.clone(),
+        second,
+        budget: budget.clone(),
+    });
+
+    let mut summary: Vec<String> = Vec::new();
+    for filename in filenames.iter() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let info = BLIFInfo::from_file(filename);
+        let mut problem = Problem::new(&info, 50, 40);
+        let name = std::path::Path::new(filename).file_stem().unwrap();
+        // every benchmark exports its fronts to <dir>/<benchmark>/<placer>
+        if let Some(dir) = &pareto_dir {
+            for (pareto, placer) in [(&mut sa_params.pareto, "sa"), (&mut ga_params.pareto, "ga")] {
+                if let Some(p) = pareto.as_mut() {
+                    p.export_dir = Some(format!("{}/{}/{}", dir, name.to_string_lossy(), placer));
+                }
+            }
+        }
+        if let Some(name) = &cost_model {
+            problem.set_cost_model(cost::from_name(name).expect("unknown cost model"));
+        }
+        // activities only matter to the power term
+        if sa_params.power.is_some() {
+            let fixed = activity_file
+                .as_ref()
+                .map(|path| analysis::read_activity_file(path))
+                .unwrap_or_default();
+            problem.set_activities(&analysis::estimate_activities(&info, &fixed));
+        }
+        if let Some(arch) = &arch {
+            let table = analysis::DelayTable::new(arch, problem.nx, problem.ny);
+            problem.set_delay_model(Box::new(table));
+        }
+        let globals = typing::detect_global_nets(&info, &problem, &global_nets);
+        problem.set_global_nets(&globals);
+        if !globals.is_empty() {
+            let names: Vec<String> = globals
+                .iter()
+                .map(|id| {
+                    let net = &problem.nets[*id];
+                    format!("{}({})", net.name, net.pins.len())
+                })
+                .collect();
+            summary.push(format!("{:24} global nets: {}", filename, names.join(" ")));
+        }
+        if let Some(path) = &weight_file {
+            let weights = problem.read_net_weights(path);
+            problem.set_net_weights(&weights);
+        }
+        if let Some(crit_exp) = criticality_weights {
+            let params = algorithms::AnnealingParams {
+                schedule: algorithms::Schedule::Fixed {
+                    t_init: 5.0,
+                    t_decrease_factor: 0.8,
+                    t_terminate: 0.5,
+                },
+                inner_num: 100.,
+                check_every: None,
+                moves: moves.clone(),
+                range_limit: range_limit.clone(),
+                congestion: None,
+                timing: None,
+                power: None,
+                pareto: None,
+                route: None,
+                budget: algorithms::Budget {
+                    cancel: budget.cancel.clone(),
+                    ..algorithms::Budget::default()
+                },
+            };
+            let shared = Arc::new(problem);
+            let weights = {
+                let sol = annealing_placement(&shared, &params).best;
+                let graph = analysis::TimingGraph::new(&shared);
+                let report = graph.analyze(&sol, shared.delay_model.as_ref());
+                graph.net_weights(&report, shared.nets.len(), crit_exp)
+            };
+            problem = Arc::try_unwrap(shared)
+                .ok()
+                .expect("the problem is still shared");
+            // on top of the weights from --net-weights
+            let weights: Vec<f64> = problem
+                .nets
+                .iter()
+                .zip(weights.iter())
+                .map(|(net, w)| net.weight * w)
+                .collect();
+            problem.set_net_weights(&weights);
+        }
+        let problem = Arc::new(problem);
+        println!("{}   cost model: {}", filename, problem.cost_model.name());
+
+        let write_history = |placer: &str, steps: &algorithms::MemoryObserver| {
+            if let Some(dir) = &history_dir {
+                std::fs::create_dir_all(dir).expect("cannot create history directory");
+                let path = format!("{}/{}_{}.csv", dir, name.to_string_lossy(), placer);
+                steps.write_csv(&path).expect("cannot write history");
+            }
+        };
+
+        // cost = 1394 ; time = 9.57s
+        let mut steps = algorithms::MemoryObserver::default();
+        let sa = if history_dir.is_some() {
+            annealing_placement_with(&problem, &sa_params, &mut steps)
+        } else if quiet {
+            annealing_placement(&problem, &sa_params)
+        } else {
+            annealing_placement_with(&problem, &sa_params, &mut algorithms::ConsoleObserver)
+        };
+        write_history("sa", &steps);
+        sa.print();
+        report(
+            &sa.best,
+            sa_params.congestion.as_ref(),
+            sa_params.power.as_ref(),
+        );
+        if let (Some(p), Some(a)) = (&sa_params.pareto, &sa.pareto) {
+            a.report(p);
+        }
+        if let Some(routing) = &sa.routing {
+            routing.print();
+        }
+        // cost =  ; time =
+        let mut steps = algorithms::MemoryObserver::default();
+        let ga = if history_dir.is_some() {
+            genetic_placement_with(&problem, &ga_params, &mut steps)
+        } else if quiet {
+            genetic_placement(&problem, &ga_params)
+        } else {
+            let mut observer =
+                algorithms::Throttled::new(algorithms::ConsoleObserver, Duration::from_secs(1));
+            genetic_placement_with(&problem, &ga_params, &mut observer)
+        };
+        write_history("ga", &steps);
+        ga.print();
+        report(&ga.best, None, None);
+        if let (Some(p), Some(a)) = (&ga_params.pareto, &ga.pareto) {
+            a.report(p);
+        }
+        if let Some(routing) = &ga.routing {
+            routing.print();
+        }
+
+        for (placer, sol, stop_reason) in [
+            ("sa", sa.best, sa.stop_reason),
+            ("ga", ga.best, ga.stop_reason),
+        ] {
+            let mut line = format!(
+                "{:24} {}   {}={:.0}",
+                filename,
+                placer,
+                problem.cost_model.name(),
+                sol.cost_panic()
+            );
+            // after a ctrl-c the program only reports what it has
+            if min_width && !cancel.is_cancelled() {
+                let (width, _) = analysis::min_channel_width(&sol, &search_from);
+                line.push_str(&format!("   w_min={}", width));
+            }
+            if stop_reason != algorithms::StopReason::Completed {
+                line.push_str(&format!("   stopped={:?}", stop_reason));
+            }
+            summary.push(line);
+        }
+        if let Some(params) = &nsga2_params {
+            let result = nsga2_placement(&problem, params);
+            result.print();
+            let front = &result.front;
+            let mut line = format!(
+                "{:24} nsga2   front={}   {}={:.0}..{:.0}",
+                filename,
+                front.len(),
+                problem.cost_model.name(),
+                front[0].0[0],
+                front[front.len() - 1].0[0]
+            );
+            if result.stop_reason != algorithms::StopReason::Completed {
+                line.push_str(&format!("   stopped={:?}", result.stop_reason));
+            }
+            summary.push(line);
+        }
+    }
+    summary.iter().for_each(|line| println!("{}", line));
 
     // genetic_placement(&info, 50, 40, 100, 30, 3, 10);
     // annealing_placement(&info, 35, 35, 5., 0.9, 0.01);
 }
+
+// cancel `token` on the first SIGINT and kill the program on the second one
+#[cfg(unix)]
+fn cancel_on_sigint(token: &algorithms::CancelToken) {
+    let token = token.clone();
+    // ctrlc calls this on a thread of its own, not in the signal handler,
+    // so it need not be async-signal-safe
+    ctrlc::set_handler(move || {
+        if token.is_cancelled() {
+            std::process::exit(130);
+        }
+        token.cancel();
+    })
+    .expect("cannot handle SIGINT");
+}
+
+#[cfg(not(unix))]
+fn cancel_on_sigint(_token: &algorithms::CancelToken) {}
@@ -0,0 +1,69 @@
+use std::fs;
+
+// a wire type of the routing channels, spanning `length` tiles
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub length: usize,
+    pub delay: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Architecture {
+    pub lut_delay: f64,
+    // through a routing switch between two wires
+    pub switch_delay: f64,
+    // from a cell output onto a wire, and from a wire into a cell input
+    pub opin_delay: f64,
+    pub ipin_delay: f64,
+    pub segments: Vec<Segment>,
+}
+
+impl Architecture {
+    // one `key value...` pair per line, `#` starts a comment:
+    //   lut_delay 1.0
+    //   segment 4 0.3
+    pub fn from_file(path: &str) -> Self {
+        let content = fs::read_to_string(path).expect("cannot read file");
+        let mut arch = Architecture {
+            lut_delay: 0.,
+            switch_delay: 0.,
+            opin_delay: 0.,
+            ipin_delay: 0.,
+            segments: Vec::new(),
+        };
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap();
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let value = |i: usize| -> f64 {
+                tokens
+                    .get(i)
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or_else(|| panic!("invalid architecture line: {}", line))
+            };
+            match tokens.first() {
+                None => {}
+                Some(&"lut_delay") => arch.lut_delay = value(1),
+                Some(&"switch_delay") => arch.switch_delay = value(1),
+                Some(&"opin_delay") => arch.opin_delay = value(1),
+                Some(&"ipin_delay") => arch.ipin_delay = value(1),
+                Some(&"segment") => arch.segments.push(Segment {
+                    length: value(1) as usize,
+                    delay: value(2),
+                }),
+                Some(key) => panic!("unknown architecture key {}", key),
+            }
+        }
+        if arch.segments.is_empty() {
+            panic!("architecture has no routing segments");
+        }
+        arch
+    }
+}
+
+#[test]
+fn should_read_architecture() {
+    let arch = Architecture::from_file("benchmarks/simple.arch");
+    assert_eq!(arch.lut_delay, 1.0);
+    assert_eq!(arch.segments.len(), 2);
+    assert_eq!(arch.segments[1].length, 4);
+}
@@ -3,12 +3,75 @@ use std::fs;
 use std::path::Path;
 
 pub type PinID = usize;
+
+// the single-output cover of a .names cell: the output is `on_set` for the
+// input patterns matching one of the cubes, e.g. "1-0", and !on_set otherwise
+pub struct Cover {
+    pub inputs: Vec<String>,
+    pub cubes: Vec<String>,
+    pub on_set: bool,
+}
+
+impl Cover {
+    // bit i of `minterm` is the value of input i
+    pub fn evaluate(&self, minterm: usize) -> bool {
+        let matched = self.cubes.iter().any(|cube| {
+            cube.chars().enumerate().all(|(i, c)| match c {
+                '1' => minterm >> i & 1 == 1,
+                '0' => minterm >> i & 1 == 0,
+                _ => true,
+            })
+        });
+        matched == self.on_set
+    }
+}
+
+// the nets of a .latch line: `.latch input output [type control] [init]`.
+// a missing control or NIL is None
+fn latch_signals(line: &str) -> (&str, &str, Option<&str>) {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    match tokens.as_slice() {
+        [_, input, output, _, control, ..] if *control != "NIL" => (input, output, Some(control)),
+        [_, input, output, ..] => (input, output, None),
+        _ => panic!("invalid latch: {}", line),
+    }
+}
+
+// cells are the .names and .latch lines, in the order they appear
 pub struct BLIFInfo {
     pub filename: String,
     pub net_list: HashMap<String, Vec<PinID>>,
+    // the .names cell driving each net; nets without one are primary inputs
+    // or latch outputs, where timing paths and activities start
+    pub drivers: HashMap<String, PinID>,
+    pub outputs: Vec<String>,
+    // the logic function of every cell; a latch passes its input on
+    pub covers: Vec<Cover>,
+    // clock or enable signals of the latches
+    pub latch_controls: Vec<String>,
     pub n_pin: usize,
 }
 
+// join lines continued with a trailing backslash
+fn logical_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for line in content.lines() {
+        if let Some(stripped) = line.trim_end().strip_suffix('\\') {
+            current.push_str(stripped);
+            current.push(' ');
+        } else {
+            current.push_str(line);
+            lines.push(current);
+            current = String::new();
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
 impl BLIFInfo {
     pub fn from_file(path: &str) -> Self {
         let content = fs::read_to_string(path).expect("cannot read file");
@@ -20,24 +83,107 @@ impl BLIFInfo {
             .expect("cannot cast os_str to str")
             .to_string();
 
-        let pins: Vec<_> = content
-            .lines()
-            .filter(|line| line.starts_with(".names"))
-            .collect();
+        let lines = logical_lines(&content);
+        let is_cell = |line: &str| line.starts_with(".names") || line.starts_with(".latch");
+        let pins: Vec<_> = lines.iter().filter(|line| is_cell(line)).collect();
         let mut net_list = HashMap::new();
+        let mut drivers = HashMap::new();
         for (pin_id, pin) in pins.iter().enumerate() {
-            let nets = pin.split_whitespace().skip(1);
-            for net in nets {
+            let is_latch = pin.starts_with(".latch");
+            let nets: Vec<&str> = if is_latch {
+                let (input, output, control) = latch_signals(pin);
+                let mut nets = vec![input, output];
+                nets.extend(control);
+                nets
+            } else {
+                pin.split_whitespace().skip(1).collect()
+            };
+            for net in nets.iter() {
                 net_list
                     .entry(net.to_string())
                     .or_insert_with(Vec::new)
                     .push(pin_id);
             }
+            // the last signal of a .names line is its output
+            match nets.last() {
+                Some(output) if !is_latch => {
+                    drivers.insert(output.to_string(), pin_id);
+                }
+                _ => {}
+            }
         }
 
+        let mut covers = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            if line.starts_with(".latch") {
+                covers.push(Cover {
+                    inputs: vec![latch_signals(line).0.to_string()],
+                    cubes: vec![String::from("1")],
+                    on_set: true,
+                });
+                continue;
+            }
+            if !line.starts_with(".names") {
+                continue;
+            }
+            let mut signals: Vec<String> = line
+                .split_whitespace()
+                .skip(1)
+                .map(|s| s.to_string())
+                .collect();
+            // the last signal is the output
+            signals.pop().expect("empty .names line");
+            let mut cubes = Vec::new();
+            // a cover without cubes is constant 0
+            let mut on_set = true;
+            for row in lines[i + 1..].iter() {
+                let row = row.trim();
+                if row.starts_with('.') {
+                    break;
+                }
+                if row.is_empty() || row.starts_with('#') {
+                    continue;
+                }
+                let tokens: Vec<&str> = row.split_whitespace().collect();
+                // constant cells only list the output value
+                let (cube, value) = match tokens.as_slice() {
+                    [value] => ("", *value),
+                    [cube, value] => (*cube, *value),
+                    _ => panic!("invalid cube: {}", row),
+                };
+                cubes.push(cube.to_string());
+                on_set = value == "1";
+            }
+            covers.push(Cover {
+                inputs: signals,
+                cubes,
+                on_set,
+            });
+        }
+
+        let mut latch_controls: Vec<String> = Vec::new();
+        for line in lines.iter().filter(|line| line.starts_with(".latch")) {
+            if let Some(control) = latch_signals(line).2 {
+                if !latch_controls.iter().any(|c| c == control) {
+                    latch_controls.push(control.to_string());
+                }
+            }
+        }
+
+        let outputs = lines
+            .iter()
+            .filter(|line| line.starts_with(".outputs"))
+            .flat_map(|line| line.split_whitespace().skip(1))
+            .map(|net| net.to_string())
+            .collect();
+
         BLIFInfo {
             filename,
             net_list,
+            drivers,
+            outputs,
+            covers,
+            latch_controls,
             n_pin: pins.len(),
         }
     }
@@ -63,4 +209,24 @@ mod tests {
         let blif = BLIFInfo::from_file(filename);
         blif.digest();
     }
+
+    #[test]
+    fn should_read_drivers_and_outputs() {
+        let blif = BLIFInfo::from_file("benchmarks/pair.blif");
+        // .names ns16 [323] pf6 is the first cell
+        assert_eq!(blif.drivers.get("pf6"), Some(&0));
+        assert!(!blif.drivers.contains_key("pa1"));
+        assert!(blif.outputs.contains(&String::from("pz5")));
+    }
+
+    #[test]
+    fn should_read_covers() {
+        let blif = BLIFInfo::from_file("benchmarks/alu2.blif");
+        assert_eq!(blif.covers.len(), blif.n_pin);
+        // .names pb pd pm with cubes 11 and 00
+        let xnor = &blif.covers[blif.drivers["pm"]];
+        assert_eq!(xnor.inputs, vec!["pb", "pd"]);
+        let truth: Vec<bool> = (0..4).map(|m| xnor.evaluate(m)).collect();
+        assert_eq!(truth, vec![true, false, false, true]);
+    }
 }
@@ -1,49 +1,166 @@
 use crate::typing::Coor;
 
-#[derive(Debug)]
+// a bounding box that also tracks how many pins lie on each of its edges, so
+// that it can be updated incrementally when a single pin moves (as in VPR)
+#[derive(Debug, Clone, PartialEq)]
 pub struct BoundBox {
     pub top: usize,
     pub bottom: usize,
     pub left: usize,
     pub right: usize,
+
+    pub n_top: usize,
+    pub n_bottom: usize,
+    pub n_left: usize,
+    pub n_right: usize,
     initialized: bool,
 }
 
 impl BoundBox {
     pub fn new() -> Self {
-        return Self {
+        Self {
             top: 0,
             left: 0,
             bottom: 0,
             right: 0,
+            n_top: 0,
+            n_bottom: 0,
+            n_left: 0,
+            n_right: 0,
             initialized: false,
-        };
+        }
     }
 
     pub fn add_coor(&mut self, coor: Coor) {
-        let (x, y) = coor;
+        let (x, y) = (coor.0 as usize, coor.1 as usize);
 
         if !self.initialized {
             self.top = y;
             self.bottom = y;
             self.left = x;
             self.right = x;
+            self.n_top = 1;
+            self.n_bottom = 1;
+            self.n_left = 1;
+            self.n_right = 1;
             self.initialized = true;
             return;
         }
-        if x < self.left {
-            self.left = x;
-        } else if x > self.right {
-            self.right = x;
-        }
-        if y < self.top {
-            self.top = y;
-        } else if y > self.bottom {
-            self.bottom = y;
-        }
+        add_to_edges(
+            x,
+            &mut self.left,
+            &mut self.n_left,
+            &mut self.right,
+            &mut self.n_right,
+        );
+        add_to_edges(
+            y,
+            &mut self.top,
+            &mut self.n_top,
+            &mut self.bottom,
+            &mut self.n_bottom,
+        );
+    }
+
+    // update the box after one of its pins moved from `from` to `to`.
+    // returns false if the box cannot be updated incrementally, which happens
+    // when the only pin on an edge moves inward; the caller has to rebuild it
+    pub fn move_coor(&mut self, from: Coor, to: Coor) -> bool {
+        move_on_axis(
+            from.0 as usize,
+            to.0 as usize,
+            &mut self.left,
+            &mut self.n_left,
+            &mut self.right,
+            &mut self.n_right,
+        ) && move_on_axis(
+            from.1 as usize,
+            to.1 as usize,
+            &mut self.top,
+            &mut self.n_top,
+            &mut self.bottom,
+            &mut self.n_bottom,
+        )
     }
 
     pub fn half_perimeter(&self) -> usize {
-        return (self.bottom - self.top) + (self.right - self.left);
+        (self.bottom - self.top) + (self.right - self.left)
+    }
+}
+
+fn add_to_edges(v: usize, min: &mut usize, n_min: &mut usize, max: &mut usize, n_max: &mut usize) {
+    if v < *min {
+        *min = v;
+        *n_min = 1;
+    } else if v == *min {
+        *n_min += 1;
     }
+    if v > *max {
+        *max = v;
+        *n_max = 1;
+    } else if v == *max {
+        *n_max += 1;
+    }
+}
+
+fn move_on_axis(
+    from: usize,
+    to: usize,
+    min: &mut usize,
+    n_min: &mut usize,
+    max: &mut usize,
+    n_max: &mut usize,
+) -> bool {
+    if to < from {
+        // leaving the max edge
+        if from == *max {
+            if *n_max == 1 {
+                return false;
+            }
+            *n_max -= 1;
+        }
+        // reaching or extending the min edge
+        if to < *min {
+            *min = to;
+            *n_min = 1;
+        } else if to == *min {
+            *n_min += 1;
+        }
+    } else if to > from {
+        if from == *min {
+            if *n_min == 1 {
+                return false;
+            }
+            *n_min -= 1;
+        }
+        if to > *max {
+            *max = to;
+            *n_max = 1;
+        } else if to == *max {
+            *n_max += 1;
+        }
+    }
+    true
+}
+
+#[test]
+fn should_update_bound_box_incrementally() {
+    let coors = [(0, 0), (2, 1), (3, 2), (3, 0)];
+    let mut bb = BoundBox::new();
+    coors.iter().for_each(|c| bb.add_coor(*c));
+    assert_eq!((bb.left, bb.right, bb.n_right), (0, 3, 2));
+
+    // one of the two pins on the right edge moves inward
+    assert!(bb.move_coor((3, 0), (1, 0)));
+    assert_eq!((bb.left, bb.right, bb.n_right, bb.n_top), (0, 3, 1, 2));
+    assert_eq!(bb.half_perimeter(), 5);
+
+    // the last pin on the right edge moves inward
+    assert!(!bb.move_coor((3, 2), (2, 2)));
+
+    // a pin moves outward
+    let mut bb = BoundBox::new();
+    coors.iter().for_each(|c| bb.add_coor(*c));
+    assert!(bb.move_coor((2, 1), (2, 4)));
+    assert_eq!((bb.bottom, bb.n_bottom), (4, 1));
 }
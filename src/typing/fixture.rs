@@ -0,0 +1,16 @@
+use std::sync::Arc;
+
+use super::{BLIFInfo, Problem};
+
+pub fn alu2() -> BLIFInfo {
+    BLIFInfo::from_file("benchmarks/alu2.blif")
+}
+
+// alu2 on a 50 x 40 grid, for tests that tune the problem before sharing it
+pub fn make_problem() -> Problem {
+    Problem::new(&alu2(), 50, 40)
+}
+
+pub fn make_fixture() -> Arc<Problem> {
+    Arc::new(make_problem())
+}
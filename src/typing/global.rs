@@ -0,0 +1,131 @@
+use super::blif::BLIFInfo;
+use super::problem::Problem;
+
+// nets such as clocks and resets that use dedicated global routing
+pub struct GlobalNetParams {
+    // name patterns where `*` matches any run of characters, e.g. "*clk*"
+    pub patterns: Vec<String>,
+    // the control signals of .latch lines
+    pub latch_controls: bool,
+    // nets driving more sinks than this
+    pub fanout_threshold: Option<usize>,
+}
+
+impl Default for GlobalNetParams {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            latch_controls: true,
+            fanout_threshold: None,
+        }
+    }
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((head, rest)) => {
+            let name = match name.strip_prefix(head) {
+                Some(name) => name,
+                None => return false,
+            };
+            // try every split point for the remaining pattern
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| glob_match(rest, &name[i..]))
+        }
+    }
+}
+
+// the ids of the nets matching any of the criteria
+pub fn detect_global_nets(
+    blif: &BLIFInfo,
+    problem: &Problem,
+    params: &GlobalNetParams,
+) -> Vec<usize> {
+    problem
+        .nets
+        .iter()
+        .filter(|net| {
+            let fanout = net.pins.len() - net.driver.is_some() as usize;
+            params.patterns.iter().any(|p| glob_match(p, &net.name))
+                || (params.latch_controls && blif.latch_controls.contains(&net.name))
+                || params.fanout_threshold.is_some_and(|t| fanout > t)
+        })
+        .map(|net| net.id)
+        .collect()
+}
+
+#[test]
+fn should_match_name_patterns() {
+    assert!(glob_match("clk", "clk"));
+    assert!(glob_match("*clk*", "sys_clk_2"));
+    assert!(glob_match("rst*", "rst_n"));
+    assert!(glob_match("a*b*c", "abbc"));
+    assert!(!glob_match("rst*", "n_rst"));
+    assert!(!glob_match("a*b", "abc"));
+}
+
+#[test]
+fn should_detect_high_fanout_nets() {
+    let blif = BLIFInfo::from_file("benchmarks/pair.blif");
+    let problem = Problem::new(&blif, 50, 40);
+    #[rustfmt::skip]
+    let params = GlobalNetParams { fanout_threshold: Some(30), ..GlobalNetParams::default() };
+    let names: Vec<&str> = detect_global_nets(&blif, &problem, &params)
+        .iter()
+        .map(|id| problem.nets[*id].name.as_str())
+        .collect();
+    // pz feeds 41 cells and pb 37
+    assert_eq!(names.len(), 2);
+    assert!(names.contains(&"pz") && names.contains(&"pb"));
+}
+
+#[test]
+fn latch_controls_should_be_global_and_untimed() {
+    use crate::analysis::TimingGraph;
+    let path = std::env::temp_dir().join("latch_controls.blif");
+    #[rustfmt::skip]
+    std::fs::write(&path, ".model t\n.inputs a clk\n.outputs q\n.latch d q re clk 0\n.names a q d\n11 1\n.end\n").unwrap();
+    let blif = BLIFInfo::from_file(path.to_str().unwrap());
+    // the latch is a cell, but starts the path through its output
+    assert_eq!(blif.n_pin, 2);
+    assert_eq!(blif.net_list["clk"], vec![0]);
+    assert!(!blif.drivers.contains_key("q"));
+
+    let mut problem = Problem::new(&blif, 4, 4);
+    let globals = detect_global_nets(&blif, &problem, &GlobalNetParams::default());
+    let names: Vec<&str> = globals
+        .iter()
+        .map(|id| problem.nets[*id].name.as_str())
+        .collect();
+    assert_eq!(names, vec!["clk"]);
+
+    // a global net is left out of timing even when a cell drives it
+    let clk = globals[0];
+    problem.nets[clk].driver = Some(1);
+    problem.set_global_nets(&globals);
+    let graph = TimingGraph::new(&problem);
+    assert!(graph.connections.iter().all(|c| c.net != clk));
+    assert_eq!(graph.connections.len(), 1);
+}
+
+#[test]
+fn global_nets_should_not_count_in_cost() {
+    use crate::cost::{CostModel, Hpwl};
+    let blif = BLIFInfo::from_file("benchmarks/pair.blif");
+    let mut problem = Problem::new(&blif, 50, 40);
+    #[rustfmt::skip]
+    let params = GlobalNetParams { fanout_threshold: Some(20), ..GlobalNetParams::default() };
+    let globals = detect_global_nets(&blif, &problem, &params);
+    problem.set_global_nets(&globals);
+
+    let problem = std::sync::Arc::new(problem);
+    let mut sol = problem.make_placement();
+    let net_cost = |id: &usize| Hpwl.net_cost(&problem.nets[*id], &sol.pin2coor);
+    let all: f64 = (0..problem.nets.len()).map(|id| net_cost(&id)).sum();
+    let global: f64 = globals.iter().map(net_cost).sum();
+    assert!(global > 0.);
+    assert_eq!(sol.cost_force(), all - global);
+    assert_eq!(sol.cost_recompute(), all - global);
+}
@@ -0,0 +1,69 @@
+// the values of a vector that a move overwrote, so that rolling the move back
+// writes them back instead of recomputing anything. it records nothing
+// unless a move is open
+#[derive(Clone)]
+pub struct Journal<T> {
+    open: bool,
+    entries: Vec<(usize, T)>,
+}
+
+impl<T: Clone> Journal<T> {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn begin(&mut self) {
+        assert!(!self.open, "a move is already open");
+        self.entries.clear();
+        self.open = true;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // remember entry i before it is overwritten
+    pub fn record(&mut self, i: usize, old: &T) {
+        if self.open {
+            self.entries.push((i, old.clone()));
+        }
+    }
+
+    pub fn commit(&mut self) {
+        assert!(self.open, "no move is open");
+        self.open = false;
+    }
+
+    // write the recorded values back, the latest first, so that an entry
+    // overwritten twice ends up with its oldest value
+    pub fn rollback(&mut self, values: &mut [T]) {
+        assert!(self.open, "no move is open");
+        for (i, old) in self.entries.drain(..).rev() {
+            values[i] = old;
+        }
+        self.open = false;
+    }
+}
+
+#[test]
+fn rollback_should_restore_the_oldest_values() {
+    let mut values = vec![1, 2, 3];
+    let mut journal = Journal::new();
+    journal.record(0, &values[0]);
+    journal.begin();
+    for (i, v) in [(0, 10), (2, 30), (0, 100)] {
+        journal.record(i, &values[i]);
+        values[i] = v;
+    }
+    journal.rollback(&mut values);
+    assert_eq!(values, vec![1, 2, 3]);
+
+    journal.begin();
+    journal.record(1, &values[1]);
+    values[1] = 20;
+    journal.commit();
+    assert_eq!(values, vec![1, 20, 3]);
+}
@@ -1,24 +1,52 @@
+mod arch;
 mod blif;
 mod bound_box;
+#[cfg(test)]
+pub mod fixture;
+mod global;
+mod journal;
 mod placement;
 mod problem;
 
-pub use blif::BLIFInfo;
+pub use arch::Architecture;
+pub use blif::{BLIFInfo, Cover};
 pub use bound_box::BoundBox;
+pub use global::{detect_global_nets, GlobalNetParams};
+pub use journal::Journal;
 pub use placement::Placement;
-pub use problem::make_coors;
 pub use problem::Problem;
 
-pub type Coor = (usize, usize);
+// u16 keeps the site of every pin in 4 bytes
+pub type Coor = (u16, u16);
 pub type PinID = usize;
 
 pub struct Net {
     pub name: String,
     pub id: usize,
     pub pins: Vec<PinID>,
+    // None for primary inputs
+    pub driver: Option<PinID>,
+    pub is_output: bool,
+    // expected transitions per clock cycle
+    pub activity: f64,
+    // scales the cost of the net
+    pub weight: f64,
+    // routed on a dedicated network and left out of the cost
+    pub is_global: bool,
 }
 
-pub struct Pin {
-    pub id: usize,
-    pub net_ids: Vec<usize>,
+impl Net {
+    // an internal net without a driver that weighs 1
+    pub fn new(id: usize, name: &str, pins: Vec<PinID>) -> Self {
+        Self {
+            name: String::from(name),
+            id,
+            pins,
+            driver: None,
+            is_output: false,
+            activity: 0.,
+            weight: 1.,
+            is_global: false,
+        }
+    }
 }
@@ -1,109 +1,454 @@
 use rand::seq::SliceRandom;
+use std::sync::Arc;
 
 use super::bound_box::BoundBox;
+use super::journal::Journal;
 use super::problem::Problem;
 use super::{Coor, PinID};
+use crate::cost::CostModel;
 
+// marks a site without a pin in the grid
+const EMPTY: u32 = u32::MAX;
+// nets with at most this many pins rebuild their box from the pins on every
+// move: it is as cheap as the edge bookkeeping, which for them mostly ends
+// in a rebuild anyway
+const SMALL_NET: usize = 3;
+
+// an invariant of a placement that does not hold, as reported by `validate`.
+// there are no site types or fixed cells yet, so every pin may sit anywhere
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    // the site of a pin is off the chip
+    OutOfBounds { pin: PinID, coor: Coor },
+    // the grid does not hold the pin on its site, e.g. because another pin
+    // was placed there too
+    Misplaced { pin: PinID, coor: Coor },
+    // a site holds a pin placed somewhere else, or no such pin
+    StaleSite { coor: Coor, pin: PinID },
+    StaleCost { cached: f64, actual: f64 },
+    StaleBoundBox { net: usize },
+}
+
+// placements share their problem, so they can be kept, returned and sent
+// to other threads independently of whoever built them
 #[derive(Clone)]
-pub struct Placement<'a> {
-    pub problem: &'a Problem,
+pub struct Placement {
+    pub problem: Arc<Problem>,
 
     pub pin2coor: Vec<Coor>,
-    pub coor2pin: Vec<Vec<Option<PinID>>>,
+    // the pin on every site, indexed x * ny + y; read it through `coor2pin`
+    grid: Vec<u32>,
+
+    pub _cost: Option<f64>,
+    // bounding box and cost of every net; only valid while `_cost` is Some
+    net_bbs: Vec<BoundBox>,
+    net_costs: Vec<f64>,
 
-    pub _cost: Option<usize>,
+    // what the open move overwrote, see `begin_move`
+    transaction: Transaction,
 }
 
-impl<'a> Placement<'a> {
-    pub fn new(problem: &'a Problem) -> Self {
+#[derive(Clone)]
+struct Transaction {
+    cost: Option<f64>,
+    grid: Journal<u32>,
+    pin2coor: Journal<Coor>,
+    net_bbs: Journal<BoundBox>,
+    net_costs: Journal<f64>,
+}
+
+impl Placement {
+    pub fn new(problem: &Arc<Problem>) -> Self {
         let cell_assignment: Vec<Coor> = problem
             .coors
             .choose_multiple(&mut rand::thread_rng(), problem.n_pin)
-            .map(|i| *i)
+            .copied()
             .collect();
 
-        let mut grid: Vec<Vec<Option<PinID>>> = vec![vec![None; problem.ny]; problem.nx];
-        for (i_pin, coor) in cell_assignment.iter().enumerate() {
-            let (x, y) = *coor;
-            grid[x][y] = Some(i_pin);
+        let mut sol = Self::empty(problem);
+        sol.set_coors(&cell_assignment);
+        sol
+    }
+
+    #[cfg(test)]
+    pub fn from_grid(problem: &Arc<Problem>, coor2pin: Vec<Vec<Option<PinID>>>) -> Self {
+        let mut pin2coor: Vec<Coor> = vec![(0, 0); problem.n_pin];
+        for (x, column) in coor2pin.iter().enumerate() {
+            for (y, pin) in column.iter().enumerate() {
+                if let Some(pin) = pin {
+                    pin2coor[*pin] = (x as u16, y as u16);
+                }
+            }
         }
 
+        let mut sol = Self::empty(problem);
+        sol.set_coors(&pin2coor);
+        sol
+    }
+
+    fn empty(problem: &Arc<Problem>) -> Self {
         Self {
-            problem: problem,
-            coor2pin: grid,
-            pin2coor: cell_assignment,
+            problem: Arc::clone(problem),
+            grid: vec![EMPTY; problem.nx * problem.ny],
+            pin2coor: vec![(0, 0); problem.n_pin],
             _cost: None,
+            net_bbs: Vec::new(),
+            net_costs: Vec::new(),
+            transaction: Transaction {
+                cost: None,
+                grid: Journal::new(),
+                pin2coor: Journal::new(),
+                net_bbs: Journal::new(),
+                net_costs: Journal::new(),
+            },
+        }
+    }
+
+    // the pin on every site as nested columns, the layout `from_grid` takes
+    #[allow(dead_code)]
+    pub fn to_grid(&self) -> Vec<Vec<Option<PinID>>> {
+        (0..self.problem.nx)
+            .map(|x| {
+                (0..self.problem.ny)
+                    .map(|y| self.coor2pin((x as u16, y as u16)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // move every pin to the given site, dropping the cached cost. sites off
+    // the chip are kept out of the grid for `validate` to report
+    pub fn set_coors(&mut self, pin2coor: &[Coor]) {
+        self.grid.iter_mut().for_each(|pin| *pin = EMPTY);
+        for (pin, coor) in pin2coor.iter().enumerate() {
+            if self.in_bounds(*coor) {
+                let i = self.index(*coor);
+                self.grid[i] = pin as u32;
+            }
+            self.pin2coor[pin] = *coor;
         }
+        self._cost = None;
     }
 
-    pub fn swap(&mut self, ca: Coor, cb: Coor) {
-        let before_cost = self.cell_cost(ca) + self.cell_cost(cb);
+    fn in_bounds(&self, (x, y): Coor) -> bool {
+        (x as usize) < self.problem.nx && (y as usize) < self.problem.ny
+    }
 
-        let pa = self.coor2pin[ca.0][ca.1];
-        let pb = self.coor2pin[cb.0][cb.1];
+    // every pin sits on an on-chip site that the grid holds it on, every pin
+    // in the grid sits where the grid says, and the cached cost and net boxes
+    // match a recompute
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for pin in 0..self.problem.n_pin {
+            let coor = self.pin2coor[pin];
+            if !self.in_bounds(coor) {
+                violations.push(Violation::OutOfBounds { pin, coor });
+            } else if self.coor2pin(coor) != Some(pin) {
+                violations.push(Violation::Misplaced { pin, coor });
+            }
+        }
+        for (i, pin) in self.grid.iter().enumerate() {
+            if *pin == EMPTY {
+                continue;
+            }
+            let coor = ((i / self.problem.ny) as u16, (i % self.problem.ny) as u16);
+            let pin = *pin as PinID;
+            if self.pin2coor.get(pin) != Some(&coor) {
+                violations.push(Violation::StaleSite { coor, pin });
+            }
+        }
+        if let Some(cached) = self._cost {
+            let actual = self.cost_recompute();
+            // the incremental cost sums in another order
+            if (cached - actual).abs() > 1e-6 * actual.abs().max(1.) {
+                violations.push(Violation::StaleCost { cached, actual });
+            }
+            for net in 0..self.problem.nets.len() {
+                if self.net_bbs.get(net) != Some(&self.net_bound_box(net)) {
+                    violations.push(Violation::StaleBoundBox { net });
+                }
+            }
+        }
+        violations
+    }
 
-        if pa == None && pb == None {
-        } else if pa == None {
-            self.coor2pin[ca.0][ca.1] = pb;
-            self.coor2pin[cb.0][cb.1] = None;
-            self.pin2coor[pb.unwrap()] = ca;
-        } else if pb == None {
-            self.coor2pin[cb.0][cb.1] = pa;
-            self.coor2pin[ca.0][ca.1] = None;
-            self.pin2coor[pa.unwrap()] = cb;
-        } else {
-            self.coor2pin[ca.0][ca.1] = pb;
-            self.coor2pin[cb.0][cb.1] = pa;
-            self.pin2coor[pb.unwrap()] = ca;
-            self.pin2coor[pa.unwrap()] = cb;
+    // fix what `validate` reports, returning what it reported. pins keep
+    // their site unless it is off the chip or taken by a pin the grid holds
+    // there, in which case they move to a random free site. the grid is
+    // rebuilt from the pins and a cached cost is recomputed
+    #[allow(dead_code)]
+    pub fn repair(&mut self) -> Vec<Violation> {
+        let violations = self.validate();
+        if violations.is_empty() {
+            return violations;
+        }
+        let (nx, ny) = (self.problem.nx, self.problem.ny);
+        let mut pin2coor = self.pin2coor.clone();
+        let mut taken = vec![false; nx * ny];
+        let mut homeless = Vec::new();
+        // pins the grid agrees with come first
+        let (held, rest): (Vec<PinID>, Vec<PinID>) = (0..self.problem.n_pin).partition(|pin| {
+            self.in_bounds(pin2coor[*pin]) && self.coor2pin(pin2coor[*pin]) == Some(*pin)
+        });
+        for pin in held.into_iter().chain(rest) {
+            let coor = pin2coor[pin];
+            if self.in_bounds(coor) && !taken[self.index(coor)] {
+                taken[self.index(coor)] = true;
+            } else {
+                homeless.push(pin);
+            }
+        }
+        let mut free: Vec<usize> = (0..nx * ny).filter(|i| !taken[*i]).collect();
+        free.shuffle(&mut rand::thread_rng());
+        for pin in homeless {
+            let i = free.pop().expect("more pins than sites");
+            pin2coor[pin] = ((i / ny) as u16, (i % ny) as u16);
         }
 
-        let after_cost = self.cell_cost(ca) + self.cell_cost(cb);
-        self._cost = Some(self.cost_mut() - before_cost + after_cost);
+        let cached = self._cost.is_some();
+        self.set_coors(&pin2coor);
+        if cached {
+            self.cost_force();
+        }
+        violations
     }
 
-    pub fn cost_mut(&mut self) -> usize {
-        if let Some(cost) = self._cost {
-            return cost;
+    fn index(&self, (x, y): Coor) -> usize {
+        x as usize * self.problem.ny + y as usize
+    }
+
+    pub fn coor2pin(&self, coor: Coor) -> Option<PinID> {
+        match self.grid[self.index(coor)] {
+            EMPTY => None,
+            pin => Some(pin as PinID),
         }
-        self.cost_force()
     }
 
-    pub fn cost_force(&mut self) -> usize {
-        let mut hp_cost = 0;
-        for net in self.problem.nets.iter() {
-            let mut bb = BoundBox::new();
-            for pin_id in &net.pins {
-                let coor = self.pin2coor[*pin_id];
-                bb.add_coor(coor);
+    // swap the contents of two sites and return the change in cost. every net
+    // touched by the swap is evaluated once, even if both pins are on it.
+    // the returned delta is 0 while the cost is not cached
+    pub fn swap(&mut self, ca: Coor, cb: Coor) -> f64 {
+        let (ia, ib) = (self.index(ca), self.index(cb));
+        let pa = self.grid[ia];
+        let pb = self.grid[ib];
+
+        self.transaction.grid.record(ia, &pa);
+        self.transaction.grid.record(ib, &pb);
+        self.grid[ia] = pb;
+        self.grid[ib] = pa;
+        // pins are moved one after another so that a rebuilt net box never
+        // sees a pin whose own incremental update is still pending
+        if pa != EMPTY {
+            let pa = pa as usize;
+            self.transaction.pin2coor.record(pa, &self.pin2coor[pa]);
+            self.pin2coor[pa] = cb;
+            self.update_bound_boxes(pa, ca, cb);
+        }
+        if pb != EMPTY {
+            let pb = pb as usize;
+            self.transaction.pin2coor.record(pb, &self.pin2coor[pb]);
+            self.pin2coor[pb] = ca;
+            self.update_bound_boxes(pb, cb, ca);
+        }
+
+        let cost = match self._cost {
+            Some(cost) => cost,
+            None => return 0.,
+        };
+        // the nets are read from the problem while their costs are updated,
+        // so the fields are borrowed one by one
+        let Self {
+            problem,
+            pin2coor,
+            net_bbs,
+            net_costs,
+            transaction,
+            ..
+        } = &mut *self;
+        // re-evaluate a net and return how much its cost changed
+        let mut refresh_net_cost = |net_id: u32| {
+            let net_id = net_id as usize;
+            let net_cost = net_cost(problem, net_bbs, pin2coor, net_id);
+            transaction.net_costs.record(net_id, &net_costs[net_id]);
+            let delta = net_cost - net_costs[net_id];
+            net_costs[net_id] = net_cost;
+            delta
+        };
+        let pin_nets = &problem.pin_nets;
+        let mut delta = 0.;
+        if pa != EMPTY {
+            for net_id in pin_nets.row(pa as usize) {
+                delta += refresh_net_cost(*net_id);
             }
-            hp_cost += bb.half_perimeter();
         }
-        self._cost = Some(hp_cost);
-        hp_cost
+        if pb != EMPTY {
+            let shared: &[u32] = match pa {
+                EMPTY => &[],
+                pa => pin_nets.row(pa as usize),
+            };
+            for net_id in pin_nets.row(pb as usize) {
+                if !shared.contains(net_id) {
+                    delta += refresh_net_cost(*net_id);
+                }
+            }
+        }
+        self._cost = Some(cost + delta);
+        delta
     }
 
-    pub fn cost_panic(&self) -> usize {
-        self._cost.unwrap()
+    // start a move: the swaps applied until `commit` or `rollback` are
+    // journaled, so that a rollback restores the sites, net boxes and costs
+    // without re-evaluating any net
+    pub fn begin_move(&mut self) {
+        let t = &mut self.transaction;
+        t.cost = self._cost;
+        t.grid.begin();
+        t.pin2coor.begin();
+        t.net_bbs.begin();
+        t.net_costs.begin();
     }
 
-    pub fn cell_cost(&self, coor: Coor) -> usize {
-        let (x, y) = coor;
-        if let Some(pin) = self.coor2pin[x][y] {
-            let mut hp_cost = 0;
-            for net_id in self.problem.pins[pin].net_ids.iter() {
-                let mut bb = BoundBox::new();
-                for pin_id in &self.problem.nets[*net_id].pins {
-                    let coor = self.pin2coor[*pin_id];
-                    bb.add_coor(coor);
+    // swap two sites as part of the open move, returning the change in cost
+    pub fn apply(&mut self, ca: Coor, cb: Coor) -> f64 {
+        assert!(self.transaction.grid.is_open(), "no move is open");
+        self.swap(ca, cb)
+    }
+
+    // the change in cost since `begin_move`
+    #[allow(dead_code)]
+    pub fn delta(&self) -> f64 {
+        match (self._cost, self.transaction.cost) {
+            (Some(cost), Some(before)) => cost - before,
+            _ => 0.,
+        }
+    }
+
+    pub fn commit(&mut self) {
+        let t = &mut self.transaction;
+        t.grid.commit();
+        t.pin2coor.commit();
+        t.net_bbs.commit();
+        t.net_costs.commit();
+    }
+
+    pub fn rollback(&mut self) {
+        let t = &mut self.transaction;
+        t.grid.rollback(&mut self.grid);
+        t.pin2coor.rollback(&mut self.pin2coor);
+        t.net_bbs.rollback(&mut self.net_bbs);
+        t.net_costs.rollback(&mut self.net_costs);
+        self._cost = t.cost;
+    }
+
+    // the distinct nets connected to the pins on the given sites
+    pub fn nets_at(&self, coors: &[Coor]) -> Vec<usize> {
+        let mut net_ids: Vec<usize> = Vec::new();
+        for coor in coors.iter() {
+            if let Some(pin) = self.coor2pin(*coor) {
+                for net_id in self.problem.pin_nets.row(pin) {
+                    let net_id = *net_id as usize;
+                    if !net_ids.contains(&net_id) {
+                        net_ids.push(net_id);
+                    }
                 }
-                hp_cost += bb.half_perimeter();
             }
-            hp_cost
-        } else {
-            0
         }
+        net_ids
+    }
+
+    // the cached bounding box of a net; only valid while the cost is cached
+    pub fn bound_box(&self, net_id: usize) -> &BoundBox {
+        &self.net_bbs[net_id]
+    }
+
+    fn update_bound_boxes(&mut self, pin: PinID, from: Coor, to: Coor) {
+        if self._cost.is_none() {
+            return;
+        }
+        for net_id in self.problem.pin_nets.row(pin) {
+            let net_id = *net_id as usize;
+            self.transaction
+                .net_bbs
+                .record(net_id, &self.net_bbs[net_id]);
+            if self.problem.net_pins.row(net_id).len() <= SMALL_NET
+                || !self.net_bbs[net_id].move_coor(from, to)
+            {
+                self.net_bbs[net_id] = self.net_bound_box(net_id);
+            }
+        }
+    }
+
+    fn net_bound_box(&self, net_id: usize) -> BoundBox {
+        let mut bb = BoundBox::new();
+        for pin_id in self.problem.net_pins.row(net_id) {
+            bb.add_coor(self.pin2coor[*pin_id as usize]);
+        }
+        bb
+    }
+
+    pub fn cost_mut(&mut self) -> f64 {
+        if let Some(cost) = self._cost {
+            return cost;
+        }
+        self.cost_force()
+    }
+
+    pub fn cost_force(&mut self) -> f64 {
+        let n_net = self.problem.nets.len();
+        // the caches are refilled in place to keep their allocations
+        let mut net_bbs = std::mem::take(&mut self.net_bbs);
+        net_bbs.clear();
+        net_bbs.extend((0..n_net).map(|i| self.net_bound_box(i)));
+        self.net_bbs = net_bbs;
+        let mut net_costs = std::mem::take(&mut self.net_costs);
+        net_costs.clear();
+        net_costs
+            .extend((0..n_net).map(|i| net_cost(&self.problem, &self.net_bbs, &self.pin2coor, i)));
+        self.net_costs = net_costs;
+        let cost = self.net_costs.iter().sum();
+        self._cost = Some(cost);
+        cost
+    }
+
+    // the cost computed from scratch, leaving the cached values untouched
+    pub fn cost_recompute(&self) -> f64 {
+        let model = self.problem.cost_model.as_ref();
+        self.problem
+            .nets
+            .iter()
+            .filter(|net| !net.is_global)
+            .map(|net| net.weight * model.net_cost(net, &self.pin2coor))
+            .sum()
+    }
+
+    // the unweighted cost of this placement under another model, e.g. as a
+    // report metric
+    pub fn cost_with(&self, model: &dyn CostModel) -> f64 {
+        self.problem
+            .nets
+            .iter()
+            .filter(|net| !net.is_global)
+            .map(|net| model.net_cost(net, &self.pin2coor))
+            .sum()
     }
+
+    pub fn cost_panic(&self) -> f64 {
+        self._cost.unwrap()
+    }
+}
+
+// the weighted cost of a net, from its cached box if the model allows
+fn net_cost(problem: &Problem, net_bbs: &[BoundBox], pin2coor: &[Coor], net_id: usize) -> f64 {
+    let model = &problem.cost_model;
+    let net = &problem.nets[net_id];
+    if net.is_global {
+        return 0.;
+    }
+    let cost = match model.bound_box_cost(net, &net_bbs[net_id]) {
+        Some(cost) => cost,
+        None => model.net_cost(net, pin2coor),
+    };
+    net.weight * cost
 }
 
 #[test]
@@ -118,36 +463,33 @@ fn it_should_swap_correctly() {
         vec![None,    None,    Some(2)], // x=3 
     ];
 
-    let pin2coor = vec![(0, 0), (2, 1), (3, 2)];
-
     use crate::typing::Net;
     #[rustfmt::skip]
     let nets = vec![
-        Net {id: 0, name: String::from("0"), pins: vec![0, 1]},
-        Net {id: 1, name: String::from("1"), pins: vec![0, 2]},
+        Net::new(0, "0", vec![0, 1]),
+        Net::new(1, "1", vec![0, 2]),
     ];
 
-    #[rustfmt::skip]
-    let problem = Problem { nx, ny, nets, n_pin: 3, pins: vec![], coors: vec![] };
-    #[rustfmt::skip]
-    let mut p = Placement { problem: &problem, coor2pin, pin2coor, _cost:None };
+    let problem = Arc::new(Problem::from_nets(nx, ny, 3, nets));
+    let mut p = Placement::from_grid(&problem, coor2pin);
 
     p.swap((0, 0), (2, 1));
-    assert_eq!(p.coor2pin[0][0], Some(1));
-    assert_eq!(p.coor2pin[2][1], Some(0));
+    assert_eq!(p.coor2pin((0, 0)), Some(1));
+    assert_eq!(p.coor2pin((2, 1)), Some(0));
     assert_eq!(p.pin2coor, vec![(2, 1), (0, 0), (3, 2)]);
     p.swap((0, 0), (2, 1));
 
     p.swap((0, 0), (1, 0));
-    assert_eq!(p.coor2pin[0][0], None);
-    assert_eq!(p.coor2pin[1][0], Some(0));
+    assert_eq!(p.coor2pin((0, 0)), None);
+    assert_eq!(p.coor2pin((1, 0)), Some(0));
     assert_eq!(p.pin2coor, vec![(1, 0), (2, 1), (3, 2)]);
     p.swap((0, 0), (1, 0));
 
     p.swap((3, 1), (0, 0));
-    assert_eq!(p.coor2pin[3][1], Some(0));
-    assert_eq!(p.coor2pin[1][0], None);
+    assert_eq!(p.coor2pin((3, 1)), Some(0));
+    assert_eq!(p.coor2pin((1, 0)), None);
     assert_eq!(p.pin2coor, vec![(3, 1), (2, 1), (3, 2)]);
+    assert!(p.validate().is_empty());
 }
 
 #[test]
@@ -162,30 +504,170 @@ fn should_calculate_cost_correctly() {
         vec![None,    None,    Some(2)], // x=3 
     ];
 
-    let pin2coor = vec![(0, 0), (2, 1), (3, 2)];
-
     use crate::typing::Net;
     #[rustfmt::skip]
     let nets = vec![
-        Net {id: 0, name: String::from("0"), pins: vec![0, 1]},
-        Net {id: 1, name: String::from("1"), pins: vec![0, 2]},
+        Net::new(0, "0", vec![0, 1]),
+        Net::new(1, "1", vec![0, 2]),
     ];
 
-    #[rustfmt::skip]
-    let problem = Problem { nx, ny, nets, n_pin: 3, pins: vec![], coors: vec![] };
-    #[rustfmt::skip]
-    let mut p = Placement { problem: &problem, coor2pin, pin2coor, _cost: None };
+    let problem = Arc::new(Problem::from_nets(nx, ny, 3, nets));
+    let mut p = Placement::from_grid(&problem, coor2pin);
 
-    assert_eq!(p.cost_mut(), 8);
+    assert_eq!(p.cost_mut(), 8.);
 
     p.swap((0, 0), (2, 1));
-    assert_eq!(p.cost_mut(), 5);
+    assert_eq!(p.cost_mut(), 5.);
     p.swap((0, 0), (2, 1));
 
     p.swap((0, 0), (1, 0));
-    assert_eq!(p.cost_mut(), 6);
+    assert_eq!(p.cost_mut(), 6.);
     p.swap((0, 0), (1, 0));
 
     p.swap((3, 1), (0, 0));
-    assert_eq!(p.cost_mut(), 2);
+    assert_eq!(p.cost_mut(), 2.);
+}
+
+#[test]
+fn incremental_cost_should_match_recompute() {
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    let mut p = problem.make_placement();
+    p.cost_force();
+
+    use crate::algorithms::util::take_2;
+    for _ in 0..10_000 {
+        let (ca, cb) = take_2(&problem.coors);
+        p.swap(ca, cb);
+    }
+    assert!(p.validate().is_empty());
+    let cached = p.cost_panic();
+    assert_eq!(cached, p.cost_force());
+}
+
+#[test]
+fn net_weights_should_scale_cost() {
+    use crate::typing::fixture::make_problem;
+    let mut problem = make_problem();
+    let weights: Vec<f64> = (0..problem.nets.len())
+        .map(|i| (i % 3) as f64 * 0.5)
+        .collect();
+    problem.set_net_weights(&weights);
+    let problem = Arc::new(problem);
+    let mut p = problem.make_placement();
+    p.cost_force();
+
+    let hpwl: Vec<f64> = problem
+        .nets
+        .iter()
+        .map(|net| crate::cost::Hpwl.net_cost(net, &p.pin2coor))
+        .collect();
+    let weighted: f64 = hpwl.iter().zip(weights.iter()).map(|(c, w)| c * w).sum();
+    assert!((p.cost_panic() - weighted).abs() < 1e-6);
+
+    use crate::algorithms::util::take_2;
+    for _ in 0..1_000 {
+        let (ca, cb) = take_2(&problem.coors);
+        p.swap(ca, cb);
+    }
+    assert!((p.cost_panic() - p.cost_recompute()).abs() < 1e-6);
+}
+
+#[test]
+fn swap_should_return_delta_over_distinct_nets() {
+    use crate::typing::fixture::make_problem;
+    let mut problem = make_problem();
+    problem.set_cost_model(Box::new(crate::cost::Clique));
+    let problem = Arc::new(problem);
+    let mut p = problem.make_placement();
+    p.cost_force();
+
+    use crate::algorithms::util::take_2;
+    for _ in 0..1_000 {
+        let before = p.cost_recompute();
+        let (ca, cb) = take_2(&problem.coors);
+        let delta = p.swap(ca, cb);
+        let after = p.cost_recompute();
+        assert!((after - before - delta).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn placement_should_outlive_its_builder_and_cross_threads() {
+    use crate::typing::fixture::make_fixture;
+    let sol = {
+        let problem = make_fixture();
+        problem.make_placement()
+    };
+    let mut sol = std::thread::spawn(move || {
+        let mut sol = sol;
+        sol.cost_force();
+        sol
+    })
+    .join()
+    .unwrap();
+    assert_eq!(sol.cost_mut(), sol.cost_recompute());
+}
+
+#[test]
+fn repair_should_fix_what_validate_reports() {
+    use crate::typing::Net;
+    #[rustfmt::skip]
+    let nets = vec![
+        Net::new(0, "0", vec![0, 1, 2]),
+    ];
+    let problem = Arc::new(Problem::from_nets(4, 3, 3, nets));
+    let mut p = problem.make_placement();
+    p.cost_force();
+    assert!(p.validate().is_empty());
+
+    // pin 1 joins pin 0 on its site and pin 2 leaves the chip
+    let c0 = p.pin2coor[0];
+    p.set_coors(&[c0, c0, (4, 0)]);
+    p._cost = Some(-1.);
+    let violations = p.validate();
+    assert!(violations.contains(&Violation::Misplaced { pin: 0, coor: c0 }));
+    assert!(violations.contains(&Violation::OutOfBounds {
+        pin: 2,
+        coor: (4, 0)
+    }));
+    assert!(violations
+        .iter()
+        .any(|v| matches!(v, Violation::StaleCost { .. })));
+
+    assert_eq!(p.repair(), violations);
+    assert!(p.validate().is_empty());
+    // the pin the grid held keeps its site
+    assert_eq!(p.coor2pin(c0), Some(1));
+    assert_eq!(p.cost_panic(), p.cost_recompute());
+}
+
+#[test]
+fn rollback_should_restore_placement_without_recomputing() {
+    use crate::algorithms::util::take_2;
+    use crate::typing::fixture::make_fixture;
+    let problem = make_fixture();
+    let mut p = problem.make_placement();
+    p.cost_force();
+
+    for i in 0..200 {
+        let pin2coor = p.pin2coor.clone();
+        let cost = p.cost_panic();
+        p.begin_move();
+        let mut delta = 0.;
+        for _ in 0..3 {
+            let (ca, cb) = take_2(&problem.coors);
+            delta += p.apply(ca, cb);
+        }
+        assert!((p.delta() - delta).abs() < 1e-6);
+        if i % 2 == 0 {
+            p.commit();
+            assert!((p.cost_panic() - cost - delta).abs() < 1e-6);
+        } else {
+            p.rollback();
+            assert_eq!(p.pin2coor, pin2coor);
+            assert_eq!(p.cost_panic(), cost);
+        }
+        assert!(p.validate().is_empty());
+    }
 }
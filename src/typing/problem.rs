@@ -1,6 +1,12 @@
 use super::blif::BLIFInfo;
 use super::placement::Placement;
-use crate::typing::{Coor, Net, Pin};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+
+use crate::analysis::{Activity, DelayModel, LinearDelay};
+use crate::cost::{CostModel, Hpwl};
+use crate::typing::{Coor, Net};
 
 pub struct Problem {
     pub nx: usize,
@@ -9,14 +15,69 @@ pub struct Problem {
     pub n_pin: usize,
     pub coors: Vec<Coor>,
     pub nets: Vec<Net>,
-    pub pins: Vec<Pin>,
+    // the pins of every net and the nets of every pin, packed for the hot
+    // loops of the placers and derived from the pins of the nets
+    pub net_pins: Adjacency,
+    pub pin_nets: Adjacency,
+
+    pub cost_model: Box<dyn CostModel>,
+    // used by timing analysis and timing-driven placement
+    pub delay_model: Box<dyn DelayModel>,
+}
+
+// compressed sparse rows: the items of row i are items[start[i]..start[i + 1]]
+pub struct Adjacency {
+    start: Vec<u32>,
+    items: Vec<u32>,
+}
+
+impl Adjacency {
+    pub fn from_rows<'r, I>(rows: I) -> Self
+    where
+        I: Iterator<Item = &'r Vec<usize>>,
+    {
+        let mut start = vec![0];
+        let mut items = Vec::new();
+        for row in rows {
+            items.extend(row.iter().map(|j| *j as u32));
+            start.push(items.len() as u32);
+        }
+        Self { start, items }
+    }
+
+    // the transpose of `rows`: row j lists every i whose rows[i] holds j
+    pub fn transpose<'r, I>(n_row: usize, rows: I) -> Self
+    where
+        I: Iterator<Item = &'r Vec<usize>> + Clone,
+    {
+        let mut start = vec![0; n_row + 1];
+        for j in rows.clone().flatten() {
+            start[*j + 1] += 1;
+        }
+        for j in 0..n_row {
+            start[j + 1] += start[j];
+        }
+        let mut next = start.clone();
+        let mut items = vec![0; start[n_row] as usize];
+        for (i, row) in rows.enumerate() {
+            for j in row {
+                items[next[*j] as usize] = i as u32;
+                next[*j] += 1;
+            }
+        }
+        Self { start, items }
+    }
+
+    pub fn row(&self, i: usize) -> &[u32] {
+        &self.items[self.start[i] as usize..self.start[i + 1] as usize]
+    }
 }
 
 pub fn make_coors(nx: usize, ny: usize) -> Vec<Coor> {
     let mut coors: Vec<Coor> = Vec::new();
     for x in 0..nx {
         for y in 0..ny {
-            coors.push((x, y));
+            coors.push((x as u16, y as u16));
         }
     }
     coors
@@ -31,43 +92,91 @@ impl Problem {
                 nx * ny
             );
         }
-        let coors = make_coors(nx, ny);
-
         let mut nets: Vec<Net> = Vec::new();
         let mut i_net = 0;
         for (name, pins) in blif.net_list.iter() {
             nets.push(Net {
-                id: i_net,
-                name: name.clone(),
-                pins: pins.clone(),
+                driver: blif.drivers.get(name).copied(),
+                is_output: blif.outputs.contains(name),
+                ..Net::new(i_net, name, pins.clone())
             });
             i_net += 1;
         }
 
-        let mut pins: Vec<Pin> = Vec::new();
-        for i_pin in 0..blif.n_pin {
-            pins.push(Pin {
-                id: i_pin,
-                net_ids: Vec::new(),
-            });
+        Self::from_nets(nx, ny, blif.n_pin, nets)
+    }
+
+    pub fn from_nets(nx: usize, ny: usize, n_pin: usize, nets: Vec<Net>) -> Self {
+        // placements store pins as u32 and sites as u16
+        if n_pin >= u32::MAX as usize {
+            panic!("too many pins (n_pin={})", n_pin);
         }
-        for net in nets.iter() {
-            for pin_id in &net.pins {
-                pins[*pin_id].net_ids.push(net.id);
-            }
+        if nx > u16::MAX as usize + 1 || ny > u16::MAX as usize + 1 {
+            panic!("chip too large (nx={}, ny={})", nx, ny);
         }
-
         Self {
-            n_pin: blif.n_pin,
             nx,
             ny,
-            coors,
+            n_pin,
+            coors: make_coors(nx, ny),
+            net_pins: Adjacency::from_rows(nets.iter().map(|net| &net.pins)),
+            pin_nets: Adjacency::transpose(n_pin, nets.iter().map(|net| &net.pins)),
             nets,
-            pins,
+            cost_model: Box::new(Hpwl),
+            delay_model: Box::new(LinearDelay::default()),
+        }
+    }
+
+    pub fn set_cost_model(&mut self, cost_model: Box<dyn CostModel>) {
+        self.cost_model = cost_model;
+    }
+
+    pub fn set_delay_model(&mut self, delay_model: Box<dyn DelayModel>) {
+        self.delay_model = delay_model;
+    }
+
+    // switching activities by net name; missing nets do not switch
+    pub fn set_activities(&mut self, activities: &HashMap<String, Activity>) {
+        for net in self.nets.iter_mut() {
+            net.activity = activities.get(&net.name).map_or(0., |a| a.density);
+        }
+    }
+
+    // one `net weight` pair per line; unlisted nets weigh 1
+    pub fn read_net_weights(&self, path: &str) -> Vec<f64> {
+        let content = fs::read_to_string(path).expect("cannot read file");
+        let mut by_name = HashMap::new();
+        for line in content.lines() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.as_slice() {
+                [] => {}
+                [net, weight] => {
+                    let weight: f64 = weight.parse().expect("invalid weight");
+                    by_name.insert(net.to_string(), weight);
+                }
+                _ => panic!("invalid weight line: {}", line),
+            }
+        }
+        self.nets
+            .iter()
+            .map(|net| by_name.get(&net.name).copied().unwrap_or(1.))
+            .collect()
+    }
+
+    // weights by net id
+    pub fn set_net_weights(&mut self, weights: &[f64]) {
+        for (net, weight) in self.nets.iter_mut().zip(weights.iter()) {
+            net.weight = *weight;
+        }
+    }
+
+    pub fn set_global_nets(&mut self, net_ids: &[usize]) {
+        for net in self.nets.iter_mut() {
+            net.is_global = net_ids.contains(&net.id);
         }
     }
 
-    pub fn make_placement(&self) -> Placement {
+    pub fn make_placement(self: &Arc<Self>) -> Placement {
         Placement::new(self)
     }
 }